@@ -0,0 +1,164 @@
+use crate::error::SnapchatError;
+use crate::exports::edgee::components::data_collection::Consent;
+
+/// Consent policy
+///
+/// Decides what happens to an event, depending on the user consent:
+/// - `send`: the event is sent with all its data
+/// - `drop`: the event is not sent
+/// - `restricted`: the event is sent without any personal data, flagged for limited data use
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ConsentPolicy {
+    Send,
+    Drop,
+    Restricted,
+}
+
+impl ConsentPolicy {
+    pub fn parse(key: &str, value: &str) -> Result<Self, SnapchatError> {
+        match value.trim().to_lowercase().as_str() {
+            "send" => Ok(ConsentPolicy::Send),
+            "drop" => Ok(ConsentPolicy::Drop),
+            "restricted" => Ok(ConsentPolicy::Restricted),
+            _ => Err(SnapchatError::invalid_setting(
+                key,
+                format!("{} (expected send, drop or restricted)", value),
+            )),
+        }
+    }
+}
+
+/// Consent mode
+///
+/// The consent policy of each consent status:
+/// - `granted`: events are always sent
+/// - `pending`: the `snapchat_pending_consent_policy` setting, `drop` by default
+/// - `denied`: the `snapchat_denied_consent_policy` setting, `drop` by default
+///
+/// Events without consent use the `edgee_default_consent` setting, and are sent when it is not set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ConsentMode {
+    pub default_consent: Option<Consent>,
+    pub pending: ConsentPolicy,
+    pub denied: ConsentPolicy,
+}
+
+impl Default for ConsentMode {
+    fn default() -> Self {
+        ConsentMode {
+            default_consent: None,
+            pending: ConsentPolicy::Drop,
+            denied: ConsentPolicy::Drop,
+        }
+    }
+}
+
+impl ConsentMode {
+    /// Returns the consent policy of an event, from its consent status.
+    pub fn policy(&self, consent: Option<Consent>) -> ConsentPolicy {
+        match consent.or(self.default_consent) {
+            None | Some(Consent::Granted) => ConsentPolicy::Send,
+            Some(Consent::Pending) => self.pending,
+            Some(Consent::Denied) => self.denied,
+        }
+    }
+}
+
+/// Parse a consent status (`granted`, `pending` or `denied`).
+pub(crate) fn parse_consent(key: &str, value: &str) -> Result<Consent, SnapchatError> {
+    match value.trim().to_lowercase().as_str() {
+        "granted" => Ok(Consent::Granted),
+        "pending" => Ok(Consent::Pending),
+        "denied" => Ok(Consent::Denied),
+        _ => Err(SnapchatError::invalid_setting(
+            key,
+            format!("{} (expected granted, pending or denied)", value),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn policy_table() {
+        use ConsentPolicy::{Drop, Restricted, Send};
+
+        let granted = Some(Consent::Granted);
+        let pending = Some(Consent::Pending);
+        let denied = Some(Consent::Denied);
+        // (default consent, pending policy, denied policy, consent, expected policy)
+        let cases = [
+            (None, Drop, Drop, None, Send),
+            (None, Drop, Drop, granted, Send),
+            (None, Drop, Drop, pending, Drop),
+            (None, Drop, Drop, denied, Drop),
+            (None, Restricted, Send, pending, Restricted),
+            (None, Restricted, Send, denied, Send),
+            (None, Send, Restricted, pending, Send),
+            (None, Send, Restricted, denied, Restricted),
+            (granted, Drop, Drop, None, Send),
+            (granted, Drop, Drop, pending, Drop),
+            (granted, Drop, Drop, denied, Drop),
+            (pending, Drop, Send, None, Drop),
+            (pending, Restricted, Drop, None, Restricted),
+            (pending, Restricted, Drop, granted, Send),
+            (pending, Restricted, Drop, denied, Drop),
+            (denied, Drop, Drop, None, Drop),
+            (denied, Send, Restricted, None, Restricted),
+            (denied, Send, Restricted, pending, Send),
+            (denied, Send, Restricted, granted, Send),
+        ];
+        for (default_consent, pending, denied, consent, expected) in cases {
+            let mode = ConsentMode {
+                default_consent,
+                pending,
+                denied,
+            };
+            assert_eq!(
+                mode.policy(consent),
+                expected,
+                "mode: {:?}, consent: {:?}",
+                mode,
+                consent
+            );
+        }
+    }
+
+    #[test]
+    fn default_mode() {
+        let mode = ConsentMode::default();
+        assert_eq!(mode.policy(None), ConsentPolicy::Send);
+        assert_eq!(mode.policy(Some(Consent::Granted)), ConsentPolicy::Send);
+        assert_eq!(mode.policy(Some(Consent::Pending)), ConsentPolicy::Drop);
+        assert_eq!(mode.policy(Some(Consent::Denied)), ConsentPolicy::Drop);
+    }
+
+    #[test]
+    fn default_consent_applies_without_consent() {
+        let mode = ConsentMode {
+            default_consent: Some(Consent::Pending),
+            pending: ConsentPolicy::Restricted,
+            denied: ConsentPolicy::Drop,
+        };
+        assert_eq!(mode.policy(None), ConsentPolicy::Restricted);
+        assert_eq!(mode.policy(Some(Consent::Granted)), ConsentPolicy::Send);
+        assert_eq!(mode.policy(Some(Consent::Denied)), ConsentPolicy::Drop);
+    }
+
+    #[test]
+    fn parse_settings() {
+        assert_eq!(
+            ConsentPolicy::parse("key", " Restricted "),
+            Ok(ConsentPolicy::Restricted)
+        );
+        assert!(ConsentPolicy::parse("key", "ignore").is_err());
+        assert!(matches!(
+            parse_consent("key", "DENIED"),
+            Ok(Consent::Denied)
+        ));
+        assert!(parse_consent("key", "yes").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::SnapchatError;
+use crate::normalize::{normalize_country, normalize_state, US_STATES};
+
+/// Data processing options country code of the United States.
+const UNITED_STATES: i32 = 1;
+
+/// Data processing options state code of California.
+const CALIFORNIA: i32 = 1000;
+
+/// Data processing options
+///
+/// The limited data use options attached to an event, with the country and state codes
+/// expected by Snapchat: `1` for the United States and `1000` for California,
+/// `0` letting Snapchat geolocate the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct DataProcessingOptions {
+    pub country: i32,
+    pub state: i32,
+}
+
+/// Data processing rules
+///
+/// Decides which events carry limited data use options, from the client location
+/// (`context.client.country_code` and `context.client.region`).
+/// Rules are set with the `snapchat_ldu_rules` setting, as comma-separated countries (ISO 3166 alpha-2),
+/// optionally followed by a state (e.g. `US:CA,US:CO,US:Virginia`). A country without state matches all its states.
+#[derive(Debug, Default)]
+pub(crate) struct DataProcessingRules {
+    rules: Vec<(String, Option<String>)>,
+}
+
+impl DataProcessingRules {
+    pub fn new(rules: Option<&String>) -> Result<Self, SnapchatError> {
+        let mut data_processing_rules = DataProcessingRules::default();
+
+        let Some(rules) = rules else {
+            return Ok(data_processing_rules);
+        };
+        for rule in rules.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let (country, state) = match rule.split_once(':') {
+                Some((country, state)) => (country, Some(state)),
+                None => (rule, None),
+            };
+            let invalid = |reason: &str| {
+                SnapchatError::invalid_setting(
+                    "snapchat_ldu_rules",
+                    format!("{} ({})", rule, reason),
+                )
+            };
+
+            let country = normalize_country(country)
+                .ok_or_else(|| invalid("expected an ISO 3166 alpha-2 country code"))?;
+            let state = match state {
+                Some(state) => {
+                    let state = normalize_state(state).ok_or_else(|| invalid("empty state"))?;
+                    if country == "us" && !US_STATES.iter().any(|(code, _)| *code == state) {
+                        return Err(invalid("unknown US state"));
+                    }
+                    Some(state)
+                }
+                None => None,
+            };
+            data_processing_rules.rules.push((country, state));
+        }
+
+        Ok(data_processing_rules)
+    }
+
+    /// Returns the data processing options of a client location, when a rule matches it.
+    pub fn options(&self, country: &str, region: &str) -> Option<DataProcessingOptions> {
+        let country = normalize_country(country)?;
+        let state = normalize_state(region);
+
+        self.rules
+            .iter()
+            .find(|(rule_country, rule_state)| {
+                *rule_country == country
+                    && rule_state
+                        .as_ref()
+                        .is_none_or(|rule_state| Some(rule_state) == state.as_ref())
+            })
+            .map(|_| DataProcessingOptions {
+                country: if country == "us" { UNITED_STATES } else { 0 },
+                state: if country == "us" && state.as_deref() == Some("ca") {
+                    CALIFORNIA
+                } else {
+                    0
+                },
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn options_table() {
+        let rules =
+            DataProcessingRules::new(Some(&"US:CA, us:Colorado, US:VA, BR".to_string())).unwrap();
+        let options = |country, state| Some(DataProcessingOptions { country, state });
+        let cases = [
+            ("US", "CA", options(1, 1000)),
+            ("us", "California", options(1, 1000)),
+            ("US", "CO", options(1, 0)),
+            ("US", "Virginia", options(1, 0)),
+            ("US", "NY", None),
+            ("US", "", None),
+            ("BR", "Sao Paulo", options(0, 0)),
+            ("BR", "", options(0, 0)),
+            ("FR", "West Europe", None),
+            ("", "CA", None),
+        ];
+        for (country, region, expected) in cases {
+            assert_eq!(
+                rules.options(country, region),
+                expected,
+                "country: {}, region: {}",
+                country,
+                region
+            );
+        }
+    }
+
+    #[test]
+    fn whole_country_rule() {
+        let rules = DataProcessingRules::new(Some(&"US".to_string())).unwrap();
+        assert_eq!(
+            rules.options("US", "CA"),
+            Some(DataProcessingOptions {
+                country: 1,
+                state: 1000
+            })
+        );
+        assert_eq!(
+            rules.options("US", "Texas"),
+            Some(DataProcessingOptions {
+                country: 1,
+                state: 0
+            })
+        );
+    }
+
+    #[test]
+    fn no_rules() {
+        let rules = DataProcessingRules::default();
+        assert_eq!(rules.options("US", "CA"), None);
+    }
+
+    #[test]
+    fn invalid_settings_fail() {
+        assert!(DataProcessingRules::new(Some(&"United".to_string())).is_err());
+        assert!(DataProcessingRules::new(Some(&"US:Atlantis".to_string())).is_err());
+        assert!(DataProcessingRules::new(Some(&"US:".to_string())).is_err());
+    }
+}
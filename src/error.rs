@@ -0,0 +1,137 @@
+use std::fmt;
+
+/// Snapchat Error
+///
+/// Every error returned to Edgee is serialized as `<code>: <message>`,
+/// where `code` is stable and machine-readable (e.g. `consent_not_granted: Consent is not granted`),
+/// so that dropped events can be grouped by reason.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SnapchatError {
+    /// The user consent doesn't allow sending the event.
+    ConsentNotGranted,
+    /// The event doesn't carry enough user identifiers (email or phone number).
+    MissingIdentifier(String),
+    /// A required setting is missing.
+    MissingSetting(&'static str),
+    /// A setting is set, but its value is invalid.
+    InvalidSetting { key: String, reason: String },
+    /// The event doesn't carry the expected data (e.g. a track event without name).
+    MissingData(String),
+    /// The event carries a value that Snapchat doesn't accept (e.g. an unknown action source).
+    InvalidEventData(String),
+    /// The track name doesn't match any Snapchat event, and unmapped events are dropped.
+    EventNotMapped(String),
+    /// User events are disabled.
+    UserEventDisabled,
+    /// The event time is too old, or too far in the future.
+    EventTimeOutOfRange(String),
+}
+
+impl SnapchatError {
+    pub fn invalid_setting(key: &str, reason: impl Into<String>) -> Self {
+        SnapchatError::InvalidSetting {
+            key: key.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Stable machine-readable code of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SnapchatError::ConsentNotGranted => "consent_not_granted",
+            SnapchatError::MissingIdentifier(_) => "missing_identifier",
+            SnapchatError::MissingSetting(_) => "missing_setting",
+            SnapchatError::InvalidSetting { .. } => "invalid_setting",
+            SnapchatError::MissingData(_) => "missing_data",
+            SnapchatError::InvalidEventData(_) => "invalid_event_data",
+            SnapchatError::EventNotMapped(_) => "event_not_mapped",
+            SnapchatError::UserEventDisabled => "user_event_disabled",
+            SnapchatError::EventTimeOutOfRange(_) => "event_time_out_of_range",
+        }
+    }
+}
+
+impl fmt::Display for SnapchatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            SnapchatError::ConsentNotGranted => write!(f, "Consent is not granted"),
+            SnapchatError::MissingIdentifier(message) => write!(f, "{}", message),
+            SnapchatError::MissingSetting(key) => write!(f, "Missing setting {}", key),
+            SnapchatError::InvalidSetting { key, reason } => {
+                write!(f, "Invalid setting {}: {}", key, reason)
+            }
+            SnapchatError::MissingData(message) => write!(f, "{}", message),
+            SnapchatError::InvalidEventData(message) => write!(f, "{}", message),
+            SnapchatError::EventNotMapped(name) => {
+                write!(f, "Event {} is not mapped to a Snapchat event", name)
+            }
+            SnapchatError::UserEventDisabled => {
+                write!(
+                    f,
+                    "User events are disabled (snapchat_user_event_name is not set)"
+                )
+            }
+            SnapchatError::EventTimeOutOfRange(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SnapchatError {}
+
+impl From<SnapchatError> for String {
+    fn from(error: SnapchatError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn serialized_errors() {
+        let cases = [
+            (
+                SnapchatError::ConsentNotGranted,
+                "consent_not_granted: Consent is not granted",
+            ),
+            (
+                SnapchatError::MissingIdentifier("User properties are empty".to_string()),
+                "missing_identifier: User properties are empty",
+            ),
+            (
+                SnapchatError::MissingSetting("snapchat_pixel_id"),
+                "missing_setting: Missing setting snapchat_pixel_id",
+            ),
+            (
+                SnapchatError::invalid_setting("snapchat_auth_mode", "expected header or query"),
+                "invalid_setting: Invalid setting snapchat_auth_mode: expected header or query",
+            ),
+            (
+                SnapchatError::MissingData("Missing page data".to_string()),
+                "missing_data: Missing page data",
+            ),
+            (
+                SnapchatError::InvalidEventData("Invalid action_source: STORE".to_string()),
+                "invalid_event_data: Invalid action_source: STORE",
+            ),
+            (
+                SnapchatError::EventNotMapped("my-event".to_string()),
+                "event_not_mapped: Event my-event is not mapped to a Snapchat event",
+            ),
+            (
+                SnapchatError::UserEventDisabled,
+                "user_event_disabled: User events are disabled (snapchat_user_event_name is not set)",
+            ),
+            (
+                SnapchatError::EventTimeOutOfRange("Event time 123 is too old".to_string()),
+                "event_time_out_of_range: Event time 123 is too old",
+            ),
+        ];
+        for (error, expected) in cases {
+            assert_eq!(String::from(error), expected);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::error::SnapchatError;
+use std::collections::HashMap;
+
+/// Snapchat standard events
+///
+/// The list of standard event names accepted by Snapchat CAPI.
+/// To know more about the standard events, check the online documentation: https://developers.snap.com/api/marketing-api/Conversions-API/Parameters#event-names
+pub(crate) const STANDARD_EVENTS: &[&str] = &[
+    "ACHIEVEMENT_UNLOCKED",
+    "AD_CLICK",
+    "AD_VIEW",
+    "ADD_BILLING",
+    "ADD_CART",
+    "ADD_TO_WISHLIST",
+    "APP_OPEN",
+    "COMPLETE_TUTORIAL",
+    "CUSTOM_EVENT_1",
+    "CUSTOM_EVENT_2",
+    "CUSTOM_EVENT_3",
+    "CUSTOM_EVENT_4",
+    "CUSTOM_EVENT_5",
+    "INVITE",
+    "LEVEL_COMPLETE",
+    "LIST_VIEW",
+    "LOGIN",
+    "PAGE_VIEW",
+    "PURCHASE",
+    "RATE",
+    "RESERVE",
+    "SAVE",
+    "SEARCH",
+    "SHARE",
+    "SIGN_UP",
+    "SPENT_CREDITS",
+    "START_CHECKOUT",
+    "START_TRIAL",
+    "SUBSCRIBE",
+    "VIEW_CONTENT",
+];
+
+/// Built-in aliases
+///
+/// Common event names (mostly coming from other analytics tools) that are translated
+/// to a Snapchat standard event without any configuration.
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("add_payment_info", "ADD_BILLING"),
+    ("add_to_cart", "ADD_CART"),
+    ("begin_checkout", "START_CHECKOUT"),
+    ("checkout", "START_CHECKOUT"),
+    ("complete_registration", "SIGN_UP"),
+    ("initiate_checkout", "START_CHECKOUT"),
+    ("page", "PAGE_VIEW"),
+    ("pageview", "PAGE_VIEW"),
+    ("signup", "SIGN_UP"),
+    ("view_item", "VIEW_CONTENT"),
+    ("view_item_list", "LIST_VIEW"),
+];
+
+/// Event mapping
+///
+/// Translates Edgee track names to Snapchat event names.
+/// Matching is case-insensitive and the lookup order is:
+/// - the `snapchat_event_mapping` setting (e.g. `purchase_completed:PURCHASE,cart:ADD_CART`)
+/// - the Snapchat standard event names (e.g. `purchase` -> `PURCHASE`)
+/// - the built-in aliases (e.g. `add_to_cart` -> `ADD_CART`)
+///
+/// Unmapped names are forwarded as-is, unless `snapchat_unmapped_event_policy` is set to `drop`.
+#[derive(Debug, Default)]
+pub(crate) struct EventMapping {
+    mapping: HashMap<String, String>,
+    drop_unmapped: bool,
+}
+
+impl EventMapping {
+    pub fn new(
+        mapping: Option<&String>,
+        unmapped_policy: Option<&String>,
+    ) -> Result<Self, SnapchatError> {
+        let mut event_mapping = EventMapping::default();
+
+        if let Some(mapping) = mapping {
+            for (from, to) in
+                crate::settings::parse_setting_pairs("snapchat_event_mapping", mapping)?
+            {
+                event_mapping
+                    .mapping
+                    .insert(normalize_name(&from), standard_event(&to).unwrap_or(to));
+            }
+        }
+
+        event_mapping.drop_unmapped = match unmapped_policy.map(|p| p.trim().to_lowercase()) {
+            None => false,
+            Some(policy) if policy.is_empty() || policy == "passthrough" => false,
+            Some(policy) if policy == "drop" => true,
+            Some(policy) => {
+                return Err(SnapchatError::invalid_setting(
+                    "snapchat_unmapped_event_policy",
+                    format!("{} (expected passthrough or drop)", policy),
+                ))
+            }
+        };
+
+        Ok(event_mapping)
+    }
+
+    /// Resolve the Snapchat event name for an Edgee track name.
+    /// Returns `None` when the name is unmapped and the drop policy is enabled.
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        if let Some(mapped) = self.mapping.get(&normalize_name(name)) {
+            return Some(mapped.clone());
+        }
+
+        if let Some(standard) = standard_event(name) {
+            return Some(standard);
+        }
+
+        if self.drop_unmapped {
+            None
+        } else {
+            Some(name.to_string())
+        }
+    }
+}
+
+/// Returns the Snapchat event name of an event configured by the operator (e.g. `snapchat_user_event_name`).
+/// Standard event names are normalized (e.g. `sign_up` -> `SIGN_UP`), other names are kept as-is:
+/// neither the mapping nor the unmapped event policy apply to them.
+pub(crate) fn configured_event_name(name: &str) -> String {
+    standard_event(name).unwrap_or_else(|| name.trim().to_string())
+}
+
+/// Normalize an event name for case-insensitive matching.
+/// Spaces and dashes are considered equivalent to underscores.
+fn normalize_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            ' ' | '-' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Returns the Snapchat standard event matching the given name, if any.
+fn standard_event(name: &str) -> Option<String> {
+    let normalized = normalize_name(name);
+
+    if let Some(event) = STANDARD_EVENTS
+        .iter()
+        .find(|event| event.to_lowercase() == normalized)
+    {
+        return Some(event.to_string());
+    }
+
+    BUILTIN_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == normalized)
+        .map(|(_, event)| event.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn resolves_every_standard_event_case_insensitively() {
+        let mapping = EventMapping::default();
+        for event in STANDARD_EVENTS {
+            assert_eq!(mapping.resolve(event), Some(event.to_string()));
+            assert_eq!(
+                mapping.resolve(&event.to_lowercase()),
+                Some(event.to_string())
+            );
+            assert_eq!(
+                mapping.resolve(&event.to_lowercase().replace('_', "-")),
+                Some(event.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_builtin_aliases() {
+        let mapping = EventMapping::default();
+        assert_eq!(mapping.resolve("add_to_cart"), Some("ADD_CART".to_string()));
+        assert_eq!(mapping.resolve("Add To Cart"), Some("ADD_CART".to_string()));
+        assert_eq!(
+            mapping.resolve("begin_checkout"),
+            Some("START_CHECKOUT".to_string())
+        );
+    }
+
+    #[test]
+    fn settings_mapping_takes_precedence() {
+        let mapping = EventMapping::new(
+            Some(&"Order Completed:purchase, cart:ADD_CART, purchase:CUSTOM_EVENT_1".to_string()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            mapping.resolve("order_completed"),
+            Some("PURCHASE".to_string())
+        );
+        assert_eq!(mapping.resolve("CART"), Some("ADD_CART".to_string()));
+        assert_eq!(
+            mapping.resolve("purchase"),
+            Some("CUSTOM_EVENT_1".to_string())
+        );
+    }
+
+    #[test]
+    fn settings_mapping_allows_custom_targets() {
+        let mapping = EventMapping::new(Some(&"quiz:quiz_done".to_string()), None).unwrap();
+        assert_eq!(mapping.resolve("quiz"), Some("quiz_done".to_string()));
+    }
+
+    #[test]
+    fn unmapped_events_are_passed_through_by_default() {
+        let mapping = EventMapping::new(None, Some(&"passthrough".to_string())).unwrap();
+        assert_eq!(mapping.resolve("my-event"), Some("my-event".to_string()));
+    }
+
+    #[test]
+    fn unmapped_events_are_dropped_with_drop_policy() {
+        let mapping = EventMapping::new(None, Some(&"DROP".to_string())).unwrap();
+        assert_eq!(mapping.resolve("my-event"), None);
+        assert_eq!(mapping.resolve("purchase"), Some("PURCHASE".to_string()));
+    }
+
+    #[test]
+    fn invalid_settings_fail() {
+        assert!(EventMapping::new(Some(&"purchase".to_string()), None).is_err());
+        assert!(EventMapping::new(Some(&"purchase:".to_string()), None).is_err());
+        assert!(EventMapping::new(None, Some(&"ignore".to_string())).is_err());
+    }
+
+    #[test]
+    fn configured_event_names() {
+        assert_eq!(configured_event_name("sign_up"), "SIGN_UP");
+        assert_eq!(configured_event_name("Add To Cart"), "ADD_CART");
+        assert_eq!(configured_event_name(" identify "), "identify");
+    }
+}
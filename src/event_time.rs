@@ -0,0 +1,193 @@
+use crate::error::SnapchatError;
+use crate::exports::edgee::components::data_collection::Event;
+
+/// Default maximum age of an event, in seconds (7 days).
+pub(crate) const DEFAULT_MAX_EVENT_AGE: u64 = 7 * 24 * 60 * 60;
+
+/// Default maximum time an event can be ahead of the current time, in seconds (5 minutes).
+pub(crate) const DEFAULT_MAX_FUTURE_SKEW: u64 = 5 * 60;
+
+/// Event time policy
+///
+/// Decides what happens to events outside of the accepted time window:
+/// - `reject`: the event is not sent
+/// - `clamp`: the event time is moved to the closest accepted time
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) enum EventTimePolicy {
+    #[default]
+    Reject,
+    Clamp,
+}
+
+impl EventTimePolicy {
+    pub fn parse(value: &str) -> Result<Self, SnapchatError> {
+        match value.trim().to_lowercase().as_str() {
+            "reject" => Ok(EventTimePolicy::Reject),
+            "clamp" => Ok(EventTimePolicy::Clamp),
+            _ => Err(SnapchatError::invalid_setting(
+                "snapchat_event_time_policy",
+                format!("{} (expected reject or clamp)", value),
+            )),
+        }
+    }
+}
+
+/// Event time guard
+///
+/// Checks that event times, in milliseconds, fall inside the window accepted by Snapchat:
+/// no older than `max_age` seconds, and no more than `max_future_skew` seconds ahead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct EventTimeGuard {
+    pub max_age: u64,
+    pub max_future_skew: u64,
+    pub policy: EventTimePolicy,
+}
+
+impl Default for EventTimeGuard {
+    fn default() -> Self {
+        EventTimeGuard {
+            max_age: DEFAULT_MAX_EVENT_AGE,
+            max_future_skew: DEFAULT_MAX_FUTURE_SKEW,
+            policy: EventTimePolicy::default(),
+        }
+    }
+}
+
+impl EventTimeGuard {
+    /// Returns the event time to send, or an error when it is out of range and rejected.
+    pub fn check(&self, event_time: i64, now: i64) -> Result<i64, SnapchatError> {
+        let oldest = now.saturating_sub(seconds_to_millis(self.max_age));
+        let latest = now.saturating_add(seconds_to_millis(self.max_future_skew));
+
+        if event_time < oldest {
+            match self.policy {
+                EventTimePolicy::Clamp => Ok(oldest),
+                EventTimePolicy::Reject => Err(SnapchatError::EventTimeOutOfRange(format!(
+                    "Event time {} is older than the maximum age of {} seconds",
+                    event_time, self.max_age
+                ))),
+            }
+        } else if event_time > latest {
+            match self.policy {
+                EventTimePolicy::Clamp => Ok(now),
+                EventTimePolicy::Reject => Err(SnapchatError::EventTimeOutOfRange(format!(
+                    "Event time {} is more than {} seconds in the future",
+                    event_time, self.max_future_skew
+                ))),
+            }
+        } else {
+            Ok(event_time)
+        }
+    }
+}
+
+/// Returns the event time in milliseconds, using the most precise timestamp available.
+pub(crate) fn event_time_millis(edgee_event: &Event) -> i64 {
+    if edgee_event.timestamp_millis > 0 {
+        edgee_event.timestamp_millis
+    } else if edgee_event.timestamp_micros > 0 {
+        edgee_event.timestamp_micros / 1000
+    } else {
+        edgee_event.timestamp.saturating_mul(1000)
+    }
+}
+
+/// Returns the current time, in milliseconds since the Unix epoch.
+pub(crate) fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn seconds_to_millis(seconds: u64) -> i64 {
+    i64::try_from(seconds)
+        .unwrap_or(i64::MAX)
+        .saturating_mul(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    #[test]
+    fn check_with_reject_policy() {
+        let guard = EventTimeGuard {
+            max_age: 3600,
+            max_future_skew: 60,
+            policy: EventTimePolicy::Reject,
+        };
+        let cases = [
+            (NOW, Ok(NOW)),
+            (NOW - 3_600_000, Ok(NOW - 3_600_000)),
+            (NOW + 60_000, Ok(NOW + 60_000)),
+            (
+                NOW - 3_600_001,
+                Err("event_time_out_of_range: Event time 1699996399999 is older than the maximum age of 3600 seconds"),
+            ),
+            (
+                NOW + 60_001,
+                Err("event_time_out_of_range: Event time 1700000060001 is more than 60 seconds in the future"),
+            ),
+            (
+                123,
+                Err("event_time_out_of_range: Event time 123 is older than the maximum age of 3600 seconds"),
+            ),
+        ];
+        for (event_time, expected) in cases {
+            assert_eq!(
+                guard.check(event_time, NOW).map_err(String::from),
+                expected.map_err(str::to_string),
+                "event time: {}",
+                event_time
+            );
+        }
+    }
+
+    #[test]
+    fn check_with_clamp_policy() {
+        let guard = EventTimeGuard {
+            max_age: 3600,
+            max_future_skew: 60,
+            policy: EventTimePolicy::Clamp,
+        };
+        let cases = [
+            (NOW - 3_600_000, NOW - 3_600_000),
+            (NOW - 3_600_001, NOW - 3_600_000),
+            (123, NOW - 3_600_000),
+            (NOW + 60_000, NOW + 60_000),
+            (NOW + 60_001, NOW),
+        ];
+        for (event_time, expected) in cases {
+            assert_eq!(
+                guard.check(event_time, NOW),
+                Ok(expected),
+                "event time: {}",
+                event_time
+            );
+        }
+    }
+
+    #[test]
+    fn check_with_huge_limits() {
+        let guard = EventTimeGuard {
+            max_age: u64::MAX,
+            max_future_skew: u64::MAX,
+            policy: EventTimePolicy::Reject,
+        };
+        assert_eq!(guard.check(0, NOW), Ok(0));
+        assert_eq!(guard.check(i64::MAX, NOW), Ok(i64::MAX));
+    }
+
+    #[test]
+    fn invalid_policy_fails() {
+        assert!(EventTimePolicy::parse("ignore").is_err());
+        assert_eq!(
+            EventTimePolicy::parse(" CLAMP "),
+            Ok(EventTimePolicy::Clamp)
+        );
+    }
+}
@@ -1,11 +1,29 @@
+mod consent;
+mod data_processing;
+mod error;
+mod event_mapping;
+mod event_time;
+mod normalize;
+mod pii;
+mod property_filter;
+mod property_types;
+mod settings;
 mod snapchat_payload;
+mod url;
+mod user_property_aliases;
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
 
 use crate::exports::edgee::components::data_collection::{
     Data, Dict, EdgeeRequest, Event, Guest, HttpMethod,
 };
-use snapchat_payload::{parse_value, SnapchatEvent, SnapchatPayload};
+use error::SnapchatError;
+use pii::{PiiAudit, PiiPolicy};
+use settings::{AuthMode, Settings};
+use snapchat_payload::{
+    consent_policy, insert_products, SnapchatEvent, SnapchatPayload, ACTION_SOURCE_PROPERTY,
+};
 
 wit_bindgen::generate!({world: "data-collection", path: ".edgee/wit", generate_all});
 
@@ -16,83 +34,183 @@ struct SnapchatComponent;
 impl Guest for SnapchatComponent {
     fn page(edgee_event: Event, settings: Dict) -> Result<EdgeeRequest, String> {
         if let Data::Page(ref data) = edgee_event.data {
-            let mut snapchat_payload = SnapchatPayload::new(settings).map_err(|e| e.to_string())?;
+            let mut snapchat_payload = SnapchatPayload::new(parse_settings(settings)?);
 
             let mut event =
-                SnapchatEvent::new(&edgee_event, "PAGE_VIEW").map_err(|e| e.to_string())?;
+                SnapchatEvent::new(&edgee_event, "PAGE_VIEW", &snapchat_payload.settings)?;
 
             // Create custom data
+            let property_types = &snapchat_payload.settings.property_types;
             let mut custom_data: HashMap<String, serde_json::Value> = HashMap::new();
 
             if !data.name.is_empty() {
-                custom_data.insert("page_name".to_string(), parse_value(&data.name));
+                custom_data.insert(
+                    "page_name".to_string(),
+                    property_types.parse("page_name", &data.name),
+                );
             }
             if !data.category.is_empty() {
-                custom_data.insert("page_category".to_string(), parse_value(&data.category));
+                custom_data.insert(
+                    "page_category".to_string(),
+                    property_types.parse("page_category", &data.category),
+                );
             }
             if !data.title.is_empty() {
-                custom_data.insert("page_title".to_string(), parse_value(&data.title));
+                custom_data.insert(
+                    "page_title".to_string(),
+                    property_types.parse("page_title", &data.title),
+                );
             }
 
             // Add custom properties from page data
             for (key, value) in data.properties.iter() {
-                custom_data.insert(key.clone(), parse_value(value));
+                custom_data.insert(key.clone(), property_types.parse(key, value));
             }
 
+            let audit = scrub_pii(&mut custom_data, &event, &snapchat_payload.settings);
+            log_pii_audit(&audit);
+            let property_filter = &snapchat_payload.settings.property_filter;
+            custom_data.retain(|key, _| property_filter.allows(key));
             event.custom_data = Some(custom_data);
             snapchat_payload.data.push(event);
 
             Ok(build_edgee_request(snapchat_payload))
         } else {
-            Err("Missing page data".to_string())
+            Err(SnapchatError::MissingData("Missing page data".to_string()).into())
         }
     }
 
     fn track(edgee_event: Event, settings: Dict) -> Result<EdgeeRequest, String> {
         if let Data::Track(ref data) = edgee_event.data {
             if data.name.is_empty() {
-                return Err("Track name is not set".to_string());
+                return Err(SnapchatError::MissingData("Track name is not set".to_string()).into());
             }
 
-            let mut snapchat_payload = SnapchatPayload::new(settings).map_err(|e| e.to_string())?;
-            let mut event =
-                SnapchatEvent::new(&edgee_event, data.name.as_str()).map_err(|e| e.to_string())?;
+            let mut snapchat_payload = SnapchatPayload::new(parse_settings(settings)?);
+            consent_policy(&edgee_event, &snapchat_payload.settings)?;
+            let event_name = match snapchat_payload.settings.event_mapping.resolve(&data.name) {
+                Some(event_name) => event_name,
+                None => return Err(SnapchatError::EventNotMapped(data.name.clone()).into()),
+            };
+            let mut event = SnapchatEvent::new(
+                &edgee_event,
+                event_name.as_str(),
+                &snapchat_payload.settings,
+            )?;
 
             // Create custom data from properties
+            let property_types = &snapchat_payload.settings.property_types;
             let mut custom_data: HashMap<String, serde_json::Value> = HashMap::new();
             for (key, value) in data.properties.iter() {
-                custom_data.insert(key.clone(), parse_value(value));
+                if key == ACTION_SOURCE_PROPERTY {
+                    continue;
+                }
+                custom_data.insert(key.clone(), property_types.parse(key, value));
             }
+            // the product-derived keys (e.g. `contents`, `item_ids`) are not scanned for personal data
+            let audit = scrub_pii(&mut custom_data, &event, &snapchat_payload.settings);
+            log_pii_audit(&audit);
+            insert_products(&mut custom_data, &data.products);
+            let property_filter = &snapchat_payload.settings.property_filter;
+            custom_data.retain(|key, _| property_filter.allows(key));
             event.custom_data = Some(custom_data);
             snapchat_payload.data.push(event);
 
             Ok(build_edgee_request(snapchat_payload))
         } else {
-            Err("Missing track data".to_string())
+            Err(SnapchatError::MissingData("Missing track data".to_string()).into())
+        }
+    }
+
+    fn user(edgee_event: Event, settings: Dict) -> Result<EdgeeRequest, String> {
+        if let Data::User(_) = edgee_event.data {
+            let mut snapchat_payload = SnapchatPayload::new(parse_settings(settings)?);
+
+            // User events are only sent when a Snapchat event name is configured
+            let Some(ref event_name) = snapchat_payload.settings.user_event_name else {
+                return Err(SnapchatError::UserEventDisabled.into());
+            };
+
+            let event = SnapchatEvent::new(
+                &edgee_event,
+                event_name.as_str(),
+                &snapchat_payload.settings,
+            )?;
+            snapchat_payload.data.push(event);
+
+            Ok(build_edgee_request(snapchat_payload))
+        } else {
+            Err(SnapchatError::MissingData("Missing user data".to_string()).into())
         }
     }
+}
+
+/// Scrub the personal data found in the custom data, and return what was done for auditing.
+/// The personal data of restricted events is always dropped.
+fn scrub_pii(
+    custom_data: &mut HashMap<String, serde_json::Value>,
+    event: &SnapchatEvent,
+    settings: &Settings,
+) -> Vec<PiiAudit> {
+    let policy = if event.restricted {
+        PiiPolicy::Drop
+    } else {
+        settings.pii_policy
+    };
+    pii::scrub_custom_data(custom_data, policy, &settings.user_property_aliases)
+}
+
+/// Log the PII audit records to stderr, the audit sink of the component.
+fn log_pii_audit(audit: &[PiiAudit]) {
+    for record in audit.iter() {
+        eprintln!("snapchat-capi: {}", record);
+    }
+}
 
-    fn user(_edgee_event: Event, _settings: Dict) -> Result<EdgeeRequest, String> {
-        Err("User event not implemented for this component".to_string())
+/// Parse and validate the component settings, and log the warnings they raise.
+/// Settings are parsed for every event, so each warning is only logged once.
+fn parse_settings(settings: Dict) -> Result<Settings, SnapchatError> {
+    let settings = Settings::new(settings)?;
+    for warning in settings.warnings.iter() {
+        if is_new_warning(warning) {
+            eprintln!("snapchat-capi: {}", warning);
+        }
     }
+    Ok(settings)
+}
+
+/// Returns whether a warning is raised for the first time by this component instance.
+fn is_new_warning(warning: &str) -> bool {
+    static LOGGED_WARNINGS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+    LOGGED_WARNINGS
+        .lock()
+        .map(|mut logged| logged.insert(warning.to_string()))
+        .unwrap_or(true)
 }
 
 fn build_edgee_request(snapchat_payload: SnapchatPayload) -> EdgeeRequest {
-    let headers = vec![(
+    let settings = &snapchat_payload.settings;
+    let mut headers = vec![(
         String::from("content-type"),
         String::from("application/json"),
     )];
 
-    let url = format!(
-        "https://tr.snapchat.com/v3/{}/events?access_token={}",
-        snapchat_payload.pixel_id, snapchat_payload.access_token
-    );
+    let mut query = vec![];
+    match settings.auth_mode {
+        AuthMode::Header => headers.push((
+            String::from("authorization"),
+            format!("Bearer {}", settings.access_token),
+        )),
+        AuthMode::Query => query.push(format!("access_token={}", settings.access_token)),
+    }
+    if let Some(ref test_code) = settings.test_event_code {
+        query.push(format!("test_event_code={}", test_code));
+    }
 
-    let url = if let Some(test_code) = snapchat_payload.test_event_code.clone() {
-        format!("{}&test_event_code={}", url, test_code)
-    } else {
-        url
-    };
+    let mut url = settings.events_url();
+    if !query.is_empty() {
+        url = format!("{}?{}", url, query.join("&"));
+    }
 
     EdgeeRequest {
         method: HttpMethod::Post,
@@ -239,9 +357,9 @@ mod tests {
     ) -> Event {
         Event {
             uuid: Uuid::new_v4().to_string(),
-            timestamp: 123,
-            timestamp_millis: 123,
-            timestamp_micros: 123,
+            timestamp: event_time::now_millis() / 1000,
+            timestamp_millis: event_time::now_millis(),
+            timestamp_micros: event_time::now_millis() * 1000,
             event_type: EventType::Page,
             data: Data::Page(sample_page_data()),
             context: sample_context(edgee_id, locale, session_start),
@@ -270,9 +388,9 @@ mod tests {
     ) -> Event {
         Event {
             uuid: Uuid::new_v4().to_string(),
-            timestamp: 123,
-            timestamp_millis: 123,
-            timestamp_micros: 123,
+            timestamp: event_time::now_millis() / 1000,
+            timestamp_millis: event_time::now_millis(),
+            timestamp_micros: event_time::now_millis() * 1000,
             event_type: EventType::Track,
             data: Data::Track(sample_track_data(event_name)),
             context: sample_context(edgee_id, locale, session_start),
@@ -288,9 +406,9 @@ mod tests {
     ) -> Event {
         Event {
             uuid: Uuid::new_v4().to_string(),
-            timestamp: 123,
-            timestamp_millis: 123,
-            timestamp_micros: 123,
+            timestamp: event_time::now_millis() / 1000,
+            timestamp_millis: event_time::now_millis(),
+            timestamp_micros: event_time::now_millis() * 1000,
             event_type: EventType::User,
             data: Data::User(sample_user_data(edgee_id.clone())),
             context: sample_context(edgee_id, locale, session_start),
@@ -307,9 +425,9 @@ mod tests {
         let user_data = sample_user_data_invalid_without_ids();
         Event {
             uuid: Uuid::new_v4().to_string(),
-            timestamp: 123,
-            timestamp_millis: 123,
-            timestamp_micros: 123,
+            timestamp: event_time::now_millis() / 1000,
+            timestamp_millis: event_time::now_millis(),
+            timestamp_micros: event_time::now_millis() * 1000,
             event_type: EventType::User,
             data: Data::User(user_data.clone()),
             context: sample_context(edgee_id, locale, session_start),
@@ -326,9 +444,9 @@ mod tests {
         let user_data = sample_user_data_invalid_without_email();
         Event {
             uuid: Uuid::new_v4().to_string(),
-            timestamp: 123,
-            timestamp_millis: 123,
-            timestamp_micros: 123,
+            timestamp: event_time::now_millis() / 1000,
+            timestamp_millis: event_time::now_millis(),
+            timestamp_micros: event_time::now_millis() * 1000,
             event_type: EventType::User,
             data: Data::User(user_data.clone()),
             context: sample_context(edgee_id, locale, session_start),
@@ -339,14 +457,20 @@ mod tests {
     fn sample_settings() -> Vec<(String, String)> {
         vec![
             ("snapchat_access_token".to_string(), "abc".to_string()),
-            ("snapchat_pixel_id".to_string(), "abc".to_string()),
+            (
+                "snapchat_pixel_id".to_string(),
+                "0c5b1d8a-3f1e-4c5a-9b1e-8f2d3c4b5a6e".to_string(),
+            ),
         ]
     }
 
     fn sample_settings_with_test_code() -> Vec<(String, String)> {
         vec![
             ("snapchat_access_token".to_string(), "abc".to_string()),
-            ("snapchat_pixel_id".to_string(), "abc".to_string()),
+            (
+                "snapchat_pixel_id".to_string(),
+                "0c5b1d8a-3f1e-4c5a-9b1e-8f2d3c4b5a6e".to_string(),
+            ),
             ("snapchat_test_event_code".to_string(), "abcd".to_string()),
         ]
     }
@@ -373,6 +497,58 @@ mod tests {
         // add more checks (headers, querystring, etc.)
     }
 
+    #[test]
+    fn page_sends_access_token_in_header() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings_with_test_code();
+        settings[0].1 = "secret-token".to_string();
+        let edgee_request = SnapchatComponent::page(event, settings).unwrap();
+
+        assert!(!edgee_request.url.contains("secret-token"));
+        assert!(!edgee_request.url.contains("access_token"));
+        assert!(edgee_request.url.ends_with("/events?test_event_code=abcd"));
+        assert!(edgee_request.headers.contains(&(
+            "authorization".to_string(),
+            "Bearer secret-token".to_string()
+        )));
+    }
+
+    #[test]
+    fn page_sends_access_token_in_query_string() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings[0].1 = "secret-token".to_string();
+        settings.push(("snapchat_auth_mode".to_string(), "query".to_string()));
+        let edgee_request = SnapchatComponent::page(event, settings).unwrap();
+
+        assert!(edgee_request
+            .url
+            .ends_with("/events?access_token=secret-token"));
+        assert!(!edgee_request
+            .headers
+            .iter()
+            .any(|(key, _)| key == "authorization"));
+    }
+
+    #[test]
+    fn page_with_invalid_auth_mode_fails() {
+        let event = sample_page_event(None, "abc".to_string(), "fr".to_string(), true);
+        let mut settings = sample_settings();
+        settings.push(("snapchat_auth_mode".to_string(), "cookie".to_string()));
+        let result = SnapchatComponent::page(event, settings);
+        assert_eq!(result.is_err(), true);
+    }
+
     #[test]
     fn page_empty_consent() {
         let event = sample_page_event(
@@ -411,6 +587,214 @@ mod tests {
                 .contains("Consent is not granted"),
             true
         );
+        assert_eq!(
+            result.err().unwrap().starts_with("consent_not_granted: "),
+            true
+        );
+    }
+
+    #[test]
+    fn page_with_default_consent() {
+        let event = sample_page_event(None, "abc".to_string(), "fr".to_string(), true);
+        let cases = [("granted", true), ("pending", false), ("denied", false)];
+        for (default_consent, sent) in cases {
+            let mut settings = sample_settings();
+            settings.push((
+                "edgee_default_consent".to_string(),
+                default_consent.to_string(),
+            ));
+            let result = SnapchatComponent::page(event.clone(), settings);
+            assert_eq!(result.is_ok(), sent, "default consent: {}", default_consent);
+        }
+
+        // the event consent takes precedence
+        let mut event = event;
+        event.consent = Some(Consent::Granted);
+        let mut settings = sample_settings();
+        settings.push(("edgee_default_consent".to_string(), "denied".to_string()));
+        assert_eq!(SnapchatComponent::page(event, settings).is_ok(), true);
+    }
+
+    #[test]
+    fn page_with_consent_policies() {
+        let settings = |pending: &str, denied: &str| {
+            let mut settings = sample_settings();
+            settings.push((
+                "snapchat_pending_consent_policy".to_string(),
+                pending.to_string(),
+            ));
+            settings.push((
+                "snapchat_denied_consent_policy".to_string(),
+                denied.to_string(),
+            ));
+            settings
+        };
+        // consent, pending policy, denied policy, expected user data (None when dropped)
+        let cases = [
+            (Consent::Pending, "send", "drop", Some(true)),
+            (Consent::Pending, "drop", "send", None),
+            (Consent::Pending, "restricted", "drop", Some(false)),
+            (Consent::Denied, "drop", "send", Some(true)),
+            (Consent::Denied, "send", "drop", None),
+            (Consent::Denied, "send", "restricted", Some(false)),
+            (Consent::Granted, "drop", "drop", Some(true)),
+            (Consent::Granted, "restricted", "restricted", Some(true)),
+        ];
+        for (consent, pending, denied, expected) in cases {
+            let event = sample_page_event(Some(consent), "abc".to_string(), "fr".to_string(), true);
+            let result = SnapchatComponent::page(event, settings(pending, denied));
+            match expected {
+                None => assert_eq!(
+                    result.err().unwrap().starts_with("consent_not_granted: "),
+                    true,
+                    "consent: {:?}, pending: {}, denied: {}",
+                    consent,
+                    pending,
+                    denied
+                ),
+                Some(with_user_data) => {
+                    let body: serde_json::Value =
+                        serde_json::from_str(&result.unwrap().body).unwrap();
+                    assert_eq!(
+                        body["data"][0]["user_data"].get("em").is_some(),
+                        with_user_data,
+                        "consent: {:?}, pending: {}, denied: {}",
+                        consent,
+                        pending,
+                        denied
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn track_with_restricted_consent() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Denied),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        event.context.page.url = "https://example.com/checkout?ScCid=abc&step=2#pay".to_string();
+        if let Data::Track(ref mut data) = event.data {
+            data.properties
+                .push(("email".to_string(), "test@test.com".to_string()));
+            data.properties
+                .push(("note".to_string(), "sent by test@test.com".to_string()));
+        }
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_denied_consent_policy".to_string(),
+            "restricted".to_string(),
+        ));
+        settings.push(("snapchat_pii_policy".to_string(), "off".to_string()));
+
+        let result = SnapchatComponent::track(event.clone(), settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let sent = &body["data"][0];
+        assert_eq!(sent["event_name"], "PURCHASE");
+        assert_eq!(sent["event_time"], event.timestamp_millis);
+        assert_eq!(sent["event_id"], event.uuid.as_str());
+        assert_eq!(sent["event_source_url"], "https://example.com/checkout");
+        assert_eq!(sent["user_data"], serde_json::json!({}));
+        assert_eq!(sent["data_processing_options"], serde_json::json!(["LDU"]));
+        assert_eq!(
+            sent["custom_data"],
+            serde_json::json!({"prop1": "value1", "prop2": 10, "currency": "USD"})
+        );
+    }
+
+    #[test]
+    fn track_with_granted_consent_has_no_data_processing_options() {
+        let event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let result = SnapchatComponent::track(event, sample_settings());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert!(body["data"][0].get("data_processing_options").is_none());
+        assert!(body["data"][0]["user_data"]
+            .get("client_ip_address")
+            .is_some());
+    }
+
+    #[test]
+    fn track_with_ldu_rules() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "en-US".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push(("snapchat_ldu_rules".to_string(), "US:CA,US:CO".to_string()));
+        let sent = |event: &Event, settings: &Vec<(String, String)>| {
+            let result = SnapchatComponent::track(event.clone(), settings.clone());
+            let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+            body["data"][0].clone()
+        };
+
+        // the client location doesn't match any rule
+        let event_data = sent(&event, &settings);
+        assert!(event_data.get("data_processing_options").is_none());
+        assert!(event_data.get("data_processing_options_country").is_none());
+        assert!(event_data.get("data_processing_options_state").is_none());
+
+        event.context.client.country_code = "US".to_string();
+        event.context.client.region = "California".to_string();
+        let event_data = sent(&event, &settings);
+        assert_eq!(
+            event_data["data_processing_options"],
+            serde_json::json!(["LDU"])
+        );
+        assert_eq!(event_data["data_processing_options_country"], 1);
+        assert_eq!(event_data["data_processing_options_state"], 1000);
+        // user data is still sent
+        assert!(event_data["user_data"].get("em").is_some());
+
+        event.context.client.region = "CO".to_string();
+        let event_data = sent(&event, &settings);
+        assert_eq!(
+            event_data["data_processing_options"],
+            serde_json::json!(["LDU"])
+        );
+        assert_eq!(event_data["data_processing_options_country"], 1);
+        assert_eq!(event_data["data_processing_options_state"], 0);
+
+        let mut settings = sample_settings();
+        settings.push(("snapchat_ldu_rules".to_string(), "US:Atlantis".to_string()));
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(
+            result.err().unwrap(),
+            "invalid_setting: Invalid setting snapchat_ldu_rules: US:Atlantis (unknown US state)"
+        );
+    }
+
+    #[test]
+    fn page_with_invalid_consent_settings_fails() {
+        let event = sample_page_event(None, "abc".to_string(), "fr".to_string(), true);
+        let cases = [
+            ("edgee_default_consent", "yes"),
+            ("snapchat_pending_consent_policy", "ignore"),
+            ("snapchat_denied_consent_policy", "ignore"),
+        ];
+        for (key, value) in cases {
+            let mut settings = sample_settings();
+            settings.push((key.to_string(), value.to_string()));
+            let result = SnapchatComponent::page(event.clone(), settings);
+            assert_eq!(
+                result.err().unwrap().starts_with("invalid_setting: "),
+                true,
+                "setting: {}",
+                key
+            );
+        }
     }
 
     #[test]
@@ -456,6 +840,10 @@ mod tests {
         let settings: Vec<(String, String)> = vec![]; // empty
         let result = SnapchatComponent::page(event, settings); // this should panic!
         assert_eq!(result.is_err(), true);
+        assert_eq!(
+            result.err().unwrap(),
+            "missing_setting: Missing setting snapchat_access_token"
+        );
     }
 
     #[test]
@@ -468,6 +856,29 @@ mod tests {
         assert_eq!(result.is_err(), true);
     }
 
+    #[test]
+    fn page_with_invalid_pixel_id_fails() {
+        let event = sample_page_event(None, "abc".to_string(), "fr".to_string(), true);
+        let settings: Vec<(String, String)> = vec![
+            ("snapchat_access_token".to_string(), "abc".to_string()),
+            ("snapchat_pixel_id".to_string(), " ".to_string()),
+        ];
+        let result = SnapchatComponent::page(event, settings);
+        assert_eq!(
+            result.err().unwrap(),
+            "invalid_setting: Invalid setting snapchat_pixel_id: must not be empty"
+        );
+    }
+
+    #[test]
+    fn page_with_unknown_setting() {
+        let event = sample_page_event(None, "abc".to_string(), "fr".to_string(), true);
+        let mut settings = sample_settings();
+        settings.push(("snapchat_pixelid".to_string(), "abc".to_string()));
+        let result = SnapchatComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+    }
+
     #[test]
     fn track_with_consent() {
         let event = sample_track_event(
@@ -486,9 +897,9 @@ mod tests {
     }
 
     #[test]
-    fn track_with_empty_name_fails() {
+    fn track_with_standard_event_name() {
         let event = sample_track_event(
-            "".to_string(),
+            "add_to_cart".to_string(),
             Some(Consent::Granted),
             "abc".to_string(),
             "fr".to_string(),
@@ -496,103 +907,220 @@ mod tests {
         );
         let settings = sample_settings();
         let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.clone().is_err(), false);
+        let edgee_request = result.unwrap();
+        assert!(edgee_request.body.contains("\"event_name\":\"ADD_CART\""));
+    }
+
+    #[test]
+    fn track_with_event_mapping() {
+        let event = sample_track_event(
+            "Order Completed".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_event_mapping".to_string(),
+            "order_completed:purchase".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.clone().is_err(), false);
+        let edgee_request = result.unwrap();
+        assert!(edgee_request.body.contains("\"event_name\":\"PURCHASE\""));
+    }
+
+    #[test]
+    fn track_with_unmapped_event_and_drop_policy_fails() {
+        let event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_unmapped_event_policy".to_string(),
+            "drop".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
         assert_eq!(result.is_err(), true);
+        assert_eq!(
+            result.err().unwrap(),
+            "event_not_mapped: Event event-name is not mapped to a Snapchat event"
+        );
     }
 
     #[test]
-    fn user_event() {
-        let event = sample_user_event(
+    fn track_with_products() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
             Some(Consent::Granted),
             "abc".to_string(),
             "fr".to_string(),
             true,
         );
+        if let Data::Track(ref mut data) = event.data {
+            data.products = vec![
+                vec![
+                    ("product_id".to_string(), "sku-1".to_string()),
+                    ("price".to_string(), "10".to_string()),
+                    ("quantity".to_string(), "2".to_string()),
+                ],
+                vec![
+                    ("product_id".to_string(), "sku-2".to_string()),
+                    ("price".to_string(), "5.5".to_string()),
+                ],
+            ];
+        }
         let settings = sample_settings();
-        let result = SnapchatComponent::user(event, settings);
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
+        assert_eq!(
+            custom_data["item_ids"],
+            serde_json::json!(["sku-1", "sku-2"])
+        );
+        assert_eq!(custom_data["num_items"], serde_json::json!(3));
+        assert_eq!(custom_data["value"], serde_json::json!(25.5));
+        assert_eq!(custom_data["currency"], serde_json::json!("USD"));
+    }
 
-        assert_eq!(result.clone().is_err(), true);
+    #[test]
+    fn warnings_are_logged_once() {
+        let warning = "Unknown setting snapchat_logged_once is ignored";
+        assert_eq!(is_new_warning(warning), true);
+        assert_eq!(is_new_warning(warning), false);
         assert_eq!(
-            result
-                .clone()
-                .err()
-                .unwrap()
-                .to_string()
-                .contains("User event not implemented for this component"),
+            is_new_warning("Unknown setting snapchat_logged_twice is ignored"),
             true
         );
     }
 
     #[test]
-    fn user_even_with_test_event_code() {
-        let event = sample_user_event(
+    fn scrub_pii_audit() {
+        let edgee_event = sample_track_event(
+            "purchase".to_string(),
             Some(Consent::Granted),
             "abc".to_string(),
             "fr".to_string(),
             true,
         );
-        let settings = sample_settings_with_test_code();
-        let result = SnapchatComponent::user(event, settings);
+        let settings = Settings::new(sample_settings()).unwrap();
+        let mut event = SnapchatEvent::new(&edgee_event, "PURCHASE", &settings).unwrap();
+        let custom_data = || {
+            HashMap::from([
+                ("email".to_string(), serde_json::json!("test@test.com")),
+                ("prop1".to_string(), serde_json::json!("value1")),
+            ])
+        };
 
-        assert_eq!(result.clone().is_err(), true);
+        let mut data = custom_data();
+        let audit = scrub_pii(&mut data, &event, &settings);
         assert_eq!(
-            result
-                .clone()
-                .err()
-                .unwrap()
-                .to_string()
-                .contains("User event not implemented for this component"),
-            true
+            audit
+                .iter()
+                .map(|record| record.to_string())
+                .collect::<Vec<_>>(),
+            vec!["custom_data property email: personal data property detected, value redacted"]
+        );
+        assert_eq!(data["email"], "[REDACTED]");
+
+        // the personal data of restricted events is dropped
+        event.restricted = true;
+        let mut data = custom_data();
+        let audit = scrub_pii(&mut data, &event, &settings);
+        assert_eq!(
+            audit
+                .iter()
+                .map(|record| record.to_string())
+                .collect::<Vec<_>>(),
+            vec!["custom_data property email: personal data property detected, property dropped"]
         );
+        assert_eq!(data.get("email"), None);
+        assert_eq!(data["prop1"], "value1");
     }
 
     #[test]
-    fn user_event_without_ids_fails() {
-        let event = sample_user_event_without_ids(
+    fn track_with_products_is_not_scrubbed() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
             Some(Consent::Granted),
             "abc".to_string(),
             "fr".to_string(),
             true,
         );
-        let settings = sample_settings();
-        let result = SnapchatComponent::user(event, settings);
-
-        assert_eq!(result.clone().is_err(), true);
+        if let Data::Track(ref mut data) = event.data {
+            data.products = vec![
+                vec![("product_id".to_string(), "978-3-16-148410-0".to_string())],
+                vec![("sku".to_string(), "415-555-2671".to_string())],
+            ];
+        }
+        let result = SnapchatComponent::track(event, sample_settings());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
         assert_eq!(
-            result
-                .clone()
-                .err()
-                .unwrap()
-                .to_string()
-                .contains("User event not implemented for this component"),
-            true
+            custom_data["item_ids"],
+            serde_json::json!(["978-3-16-148410-0", "415-555-2671"])
         );
+        assert_eq!(custom_data["contents"][1]["id"], "415-555-2671");
     }
 
     #[test]
-    fn user_event_without_email_or_phone_fails() {
-        let event = sample_user_event_without_email(
+    fn track_with_invalid_alias_keeps_valid_user_data() {
+        let mut event = sample_track_event(
+            "event-name".to_string(),
             Some(Consent::Granted),
             "abc".to_string(),
             "fr".to_string(),
             true,
         );
-        let settings = sample_settings();
-        let result = SnapchatComponent::user(event, settings);
+        let email = ("email".to_string(), "test@test.com".to_string());
+        let alias = ("mail".to_string(), "n/a".to_string());
+        for properties in [
+            vec![email.clone(), alias.clone()],
+            vec![alias.clone(), email.clone()],
+        ] {
+            event.context.user.properties = properties.clone();
+            let result = SnapchatComponent::track(event.clone(), sample_settings());
+            let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+            assert_eq!(
+                body["data"][0]["user_data"]["em"],
+                snapchat_payload::hash_value("test@test.com"),
+                "properties: {:?}",
+                properties
+            );
+        }
+    }
 
+    #[test]
+    fn track_with_invalid_email_and_no_phone_fails() {
+        let mut event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        event.context.user.properties = vec![("email".to_string(), "not an email".to_string())];
+        let settings = sample_settings();
+        let result = SnapchatComponent::track(event, settings);
         assert_eq!(result.clone().is_err(), true);
         assert_eq!(
             result
-                .clone()
                 .err()
                 .unwrap()
-                .to_string()
-                .contains("User event not implemented for this component"),
+                .contains("User properties must contain email or phone_number"),
             true
         );
     }
 
     #[test]
-    fn track_event_without_user_context_properties_fails() {
+    fn track_with_national_phone_number() {
         let mut event = sample_track_event(
             "event-name".to_string(),
             Some(Consent::Granted),
@@ -600,18 +1128,1003 @@ mod tests {
             "fr".to_string(),
             true,
         );
-        event.context.user.properties = vec![]; // empty context user properties
-        event.context.user.user_id = "".to_string(); // empty context user id
-        let settings = sample_settings();
+        event.context.user.properties =
+            vec![("phone_number".to_string(), "06 12 34 56 78".to_string())];
+
+        // the country is taken from the client context (FR)
+        let result = SnapchatComponent::track(event.clone(), sample_settings());
+        assert_eq!(result.clone().is_err(), false);
+        assert!(result
+            .unwrap()
+            .body
+            .contains(&snapchat_payload::hash_value("33612345678")));
+
+        // the setting takes precedence over the client context
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_default_phone_country".to_string(),
+            "gb".to_string(),
+        ));
         let result = SnapchatComponent::track(event, settings);
-        assert_eq!(result.clone().is_err(), true);
-        assert_eq!(
-            result
-                .clone()
-                .err()
-                .unwrap()
-                .to_string()
-                .contains("User properties are empty"),
+        assert_eq!(result.clone().is_err(), false);
+        assert!(result
+            .unwrap()
+            .body
+            .contains(&snapchat_payload::hash_value("44612345678")));
+    }
+
+    #[test]
+    fn track_with_invalid_default_phone_country_fails() {
+        let event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_default_phone_country".to_string(),
+            "France".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[test]
+    fn track_with_normalized_user_data() {
+        let event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let result = SnapchatComponent::track(event, sample_settings());
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let user_data = &body["data"][0]["user_data"];
+        assert_eq!(
+            user_data["em"],
+            snapchat_payload::hash_value("test@test.com")
+        );
+        assert_eq!(
+            user_data["ph"],
+            snapchat_payload::hash_value("391231231231")
+        );
+        assert_eq!(user_data["fn"], snapchat_payload::hash_value("john"));
+        assert_eq!(user_data["ln"], snapchat_payload::hash_value("doe"));
+        assert_eq!(user_data["ge"], snapchat_payload::hash_value("m"));
+        assert_eq!(user_data["db"], snapchat_payload::hash_value("19791231"));
+        assert_eq!(user_data["ct"], snapchat_payload::hash_value("lasvegas"));
+        assert_eq!(user_data["st"], snapchat_payload::hash_value("nv"));
+        assert_eq!(user_data["zp"], snapchat_payload::hash_value("11111"));
+        assert_eq!(user_data["country"], snapchat_payload::hash_value("us"));
+    }
+
+    #[test]
+    fn track_with_aliased_user_properties() {
+        let mut event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        event.context.user.properties = vec![
+            ("emailAddress".to_string(), "Test@Test.com".to_string()),
+            ("phone".to_string(), "+39 1231231231".to_string()),
+            ("postal_code".to_string(), "11111".to_string()),
+            ("birthday".to_string(), "1979-12-31".to_string()),
+            ("contact_city".to_string(), "Las Vegas".to_string()),
+        ];
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_user_property_aliases".to_string(),
+            "contact_city:city".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let user_data = &body["data"][0]["user_data"];
+        assert_eq!(
+            user_data["em"],
+            snapchat_payload::hash_value("test@test.com")
+        );
+        assert_eq!(
+            user_data["ph"],
+            snapchat_payload::hash_value("391231231231")
+        );
+        assert_eq!(user_data["zp"], snapchat_payload::hash_value("11111"));
+        assert_eq!(user_data["db"], snapchat_payload::hash_value("19791231"));
+        assert_eq!(user_data["ct"], snapchat_payload::hash_value("lasvegas"));
+    }
+
+    #[test]
+    fn track_with_click_id_from_url() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let click_id = |event: &Event| {
+            let result = SnapchatComponent::track(event.clone(), sample_settings());
+            let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+            body["data"][0]["user_data"]["sc_click_id"].clone()
+        };
+
+        // no click ID
+        assert_eq!(click_id(&event), serde_json::Value::Null);
+
+        // encoded, in a multi-parameter page URL
+        event.context.page.url =
+            "https://example.com/landing?utm_source=snapchat&ScCid=a1b2%2Dc3d4&utm_medium=paid#top"
+                .to_string();
+        event.context.page.search =
+            "?utm_source=snapchat&ScCid=a1b2%2Dc3d4&utm_medium=paid".to_string();
+        assert_eq!(click_id(&event), "a1b2-c3d4");
+
+        // in the referrer
+        event.context.page.url = "https://example.com/checkout".to_string();
+        event.context.page.search = String::new();
+        event.context.page.referrer = "https://example.com/landing?sccid=e5f6".to_string();
+        assert_eq!(click_id(&event), "e5f6");
+
+        // the user property takes precedence
+        event
+            .context
+            .user
+            .properties
+            .push(("sc_click_id".to_string(), "explicit".to_string()));
+        assert_eq!(click_id(&event), "explicit");
+    }
+
+    #[test]
+    fn track_with_prehashed_user_data() {
+        let mut event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let email_digest = snapchat_payload::hash_value("test@test.com");
+        let phone_digest = snapchat_payload::hash_value("33612345678");
+        event.context.user.properties = vec![
+            ("email".to_string(), email_digest.clone()),
+            (
+                "phone_number".to_string(),
+                format!("sha256:{}", phone_digest),
+            ),
+        ];
+
+        let result = SnapchatComponent::track(event.clone(), sample_settings());
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["user_data"]["em"], email_digest);
+        assert_eq!(body["data"][0]["user_data"]["ph"], phone_digest);
+
+        // the email is hashed again when hashing is forced
+        let mut settings = sample_settings();
+        settings.push(("snapchat_hash_policy".to_string(), "em:force".to_string()));
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(
+            body["data"][0]["user_data"]["em"],
+            snapchat_payload::hash_value(&email_digest)
+        );
+        assert_eq!(body["data"][0]["user_data"]["ph"], phone_digest);
+    }
+
+    #[test]
+    fn track_with_invalid_hash_policy_fails() {
+        let event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push(("snapchat_hash_policy".to_string(), "em:never".to_string()));
+        assert_eq!(
+            SnapchatComponent::track(event.clone(), settings).is_err(),
+            true
+        );
+
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_hash_policy".to_string(),
+            "email:force".to_string(),
+        ));
+        assert_eq!(SnapchatComponent::track(event, settings).is_err(), true);
+    }
+
+    #[test]
+    fn track_with_action_source() {
+        let event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+
+        // WEB by default
+        let result = SnapchatComponent::track(event.clone(), sample_settings());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["action_source"], "WEB");
+
+        // component setting
+        let mut settings = sample_settings();
+        settings.push(("snapchat_action_source".to_string(), "offline".to_string()));
+        let result = SnapchatComponent::track(event.clone(), settings.clone());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["action_source"], "OFFLINE");
+
+        // per-event override, not forwarded in custom data
+        let mut event = event;
+        if let Data::Track(ref mut data) = event.data {
+            data.properties
+                .push(("action_source".to_string(), "MOBILE_APP".to_string()));
+        }
+        let result = SnapchatComponent::track(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["action_source"], "MOBILE_APP");
+        assert!(body["data"][0]["custom_data"]
+            .get("action_source")
+            .is_none());
+    }
+
+    #[test]
+    fn track_with_property_types() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        if let Data::Track(ref mut data) = event.data {
+            data.properties
+                .push(("order_id".to_string(), "000123".to_string()));
+            data.properties
+                .push(("value".to_string(), "19.90".to_string()));
+        }
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_property_types".to_string(),
+            "prop2:string".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
+        assert_eq!(custom_data["order_id"], "000123");
+        assert_eq!(custom_data["value"], 19.9);
+        assert_eq!(custom_data["prop2"], "10");
+    }
+
+    #[test]
+    fn page_with_property_types() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_property_types".to_string(),
+            "prop3:string,prop2:integer".to_string(),
+        ));
+        let result = SnapchatComponent::page(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
+        assert_eq!(custom_data["prop2"], 10);
+        assert_eq!(custom_data["prop3"], "true");
+        assert_eq!(custom_data["prop4"], false);
+    }
+
+    #[test]
+    fn page_with_custom_data_allowlist() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_custom_data_allowlist".to_string(),
+            "prop*,currency".to_string(),
+        ));
+        settings.push((
+            "snapchat_custom_data_denylist".to_string(),
+            "prop1".to_string(),
+        ));
+        let result = SnapchatComponent::page(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = body["data"][0]["custom_data"].as_object().unwrap();
+        let mut keys: Vec<&String> = custom_data.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["currency", "prop2", "prop3", "prop4"]);
+    }
+
+    #[test]
+    fn track_with_custom_data_allowlist_and_products() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        if let Data::Track(ref mut data) = event.data {
+            data.products = vec![vec![
+                ("product_id".to_string(), "sku-1".to_string()),
+                ("price".to_string(), "10".to_string()),
+            ]];
+        }
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_custom_data_allowlist".to_string(),
+            "value,currency,item_ids".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = body["data"][0]["custom_data"].as_object().unwrap();
+        let mut keys: Vec<&String> = custom_data.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["currency", "item_ids", "value"]);
+    }
+
+    #[test]
+    fn track_with_custom_data_denylist() {
+        let event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_custom_data_denylist".to_string(),
+            "*1, currency".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
+        assert!(custom_data.get("prop1").is_none());
+        assert!(custom_data.get("currency").is_none());
+        assert_eq!(custom_data["prop2"], 10);
+    }
+
+    #[test]
+    fn page_with_canonical_event_source_url() {
+        let mut event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let result = SnapchatComponent::page(event.clone(), sample_settings());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(
+            body["data"][0]["event_source_url"],
+            "https://example.com/full-url?test=1"
+        );
+
+        event.context.page.url =
+            "https://example.com/full-url?test=1&token=abc&utm_source=snap#section".to_string();
+        event.context.page.search = "?test=1&token=abc&utm_source=snap".to_string();
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_url_strip_parameters".to_string(),
+            "test".to_string(),
+        ));
+        let result = SnapchatComponent::page(event.clone(), settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(
+            body["data"][0]["event_source_url"],
+            "https://example.com/full-url?utm_source=snap"
+        );
+
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_url_parameter_allowlist".to_string(),
+            "test".to_string(),
+        ));
+        let result = SnapchatComponent::page(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(
+            body["data"][0]["event_source_url"],
+            "https://example.com/full-url?test=1"
+        );
+    }
+
+    #[test]
+    fn page_with_invalid_property_types_fails() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_property_types".to_string(),
+            "prop2:decimal".to_string(),
+        ));
+        let result = SnapchatComponent::page(event, settings);
+        assert_eq!(
+            result.err().unwrap(),
+            "invalid_setting: Invalid setting snapchat_property_types: decimal (expected auto, string, number, integer or boolean)"
+        );
+    }
+
+    #[test]
+    fn track_with_pii_in_custom_data() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        if let Data::Track(ref mut data) = event.data {
+            data.properties
+                .push(("email".to_string(), "test@test.com".to_string()));
+            data.properties
+                .push(("contact".to_string(), "+33 6 12 34 56 78".to_string()));
+        }
+
+        // redacted by default
+        let result = SnapchatComponent::track(event.clone(), sample_settings());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
+        assert_eq!(custom_data["email"], "[REDACTED]");
+        assert_eq!(custom_data["contact"], "[REDACTED]");
+        assert_eq!(custom_data["prop1"], "value1");
+
+        let mut settings = sample_settings();
+        settings.push(("snapchat_pii_policy".to_string(), "hash".to_string()));
+        let result = SnapchatComponent::track(event.clone(), settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
+        assert_eq!(
+            custom_data["email"],
+            snapchat_payload::hash_value("test@test.com")
+        );
+
+        let mut settings = sample_settings();
+        settings.push(("snapchat_pii_policy".to_string(), "drop".to_string()));
+        let result = SnapchatComponent::track(event.clone(), settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let custom_data = &body["data"][0]["custom_data"];
+        assert!(custom_data.get("email").is_none());
+        assert!(custom_data.get("contact").is_none());
+
+        let mut settings = sample_settings();
+        settings.push(("snapchat_pii_policy".to_string(), "mask".to_string()));
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(
+            result.err().unwrap(),
+            "invalid_setting: Invalid setting snapchat_pii_policy: mask (expected hash, redact, drop or off)"
+        );
+    }
+
+    #[test]
+    fn track_without_consent_is_reported_before_other_errors() {
+        let mut event = sample_track_event(
+            "unmapped-event".to_string(),
+            Some(Consent::Denied),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        event.timestamp_millis -= 8 * 24 * 60 * 60 * 1000;
+        if let Data::Track(ref mut data) = event.data {
+            data.properties
+                .push(("action_source".to_string(), "STORE".to_string()));
+        }
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_unmapped_event_policy".to_string(),
+            "drop".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(
+            result.err().unwrap(),
+            "consent_not_granted: Consent is not granted"
+        );
+    }
+
+    #[test]
+    fn track_with_event_time() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+
+        // millisecond precision
+        let result = SnapchatComponent::track(event.clone(), sample_settings());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["event_time"], event.timestamp_millis);
+
+        // stale events are rejected by default
+        event.timestamp_millis -= 8 * 24 * 60 * 60 * 1000;
+        let result = SnapchatComponent::track(event.clone(), sample_settings());
+        assert_eq!(
+            result
+                .err()
+                .unwrap()
+                .starts_with("event_time_out_of_range: "),
+            true
+        );
+
+        // unless the maximum age allows them
+        let mut settings = sample_settings();
+        settings.push(("snapchat_max_event_age".to_string(), "30d".to_string()));
+        let result = SnapchatComponent::track(event.clone(), settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["event_time"], event.timestamp_millis);
+
+        // or they are clamped
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_event_time_policy".to_string(),
+            "clamp".to_string(),
+        ));
+        let result = SnapchatComponent::track(event, settings);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let event_time = body["data"][0]["event_time"].as_i64().unwrap();
+        let oldest = event_time::now_millis() - 7 * 24 * 60 * 60 * 1000;
+        assert!((oldest - 60_000..=oldest).contains(&event_time));
+    }
+
+    #[test]
+    fn track_with_event_id() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let event_id = |event: &Event, settings: Vec<(String, String)>| {
+            let result = SnapchatComponent::track(event.clone(), settings);
+            let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+            body["data"][0]["event_id"].clone()
+        };
+
+        // Edgee event UUID by default
+        assert_eq!(event_id(&event, sample_settings()), event.uuid.as_str());
+
+        if let Data::Track(ref mut data) = event.data {
+            data.properties
+                .push(("transaction_id".to_string(), "tx-1".to_string()));
+            data.properties
+                .push(("order_id".to_string(), "1234".to_string()));
+        }
+        assert_eq!(event_id(&event, sample_settings()), event.uuid.as_str());
+
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_event_id_properties".to_string(),
+            "event_id,transaction_id".to_string(),
+        ));
+        assert_eq!(event_id(&event, settings), "tx-1");
+
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_deterministic_event_id".to_string(),
+            "true".to_string(),
+        ));
+        assert_eq!(
+            event_id(&event, settings),
+            snapchat_payload::hash_value("PURCHASE:1234")
+        );
+    }
+
+    #[test]
+    fn track_with_invalid_action_source_fails() {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        if let Data::Track(ref mut data) = event.data {
+            data.properties
+                .push(("action_source".to_string(), "STORE".to_string()));
+        }
+        let result = SnapchatComponent::track(event, sample_settings());
+        assert_eq!(
+            result.err().unwrap(),
+            "invalid_event_data: Invalid action_source: STORE (expected WEB, MOBILE_APP or OFFLINE)"
+        );
+    }
+
+    fn sample_mobile_app_track_event() -> Event {
+        let mut event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr-FR".to_string(),
+            true,
+        );
+        event.context.client.os_name = "iOS".to_string();
+        event.context.client.os_version = "17.4".to_string();
+        event.context.client.user_agent_model = "iPhone15,2".to_string();
+        event.context.user.properties = vec![
+            (
+                "idfa".to_string(),
+                "6D92078A-8246-4BA4-AE5B-76104861E7DC".to_string(),
+            ),
+            (
+                "advertiser_tracking_enabled".to_string(),
+                "true".to_string(),
+            ),
+        ];
+        event
+    }
+
+    fn sample_settings_with_mobile_app() -> Vec<(String, String)> {
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_action_source".to_string(),
+            "MOBILE_APP".to_string(),
+        ));
+        settings.push(("snapchat_app_id".to_string(), "com.example.app".to_string()));
+        settings
+    }
+
+    #[test]
+    fn track_mobile_app_event() {
+        let event = sample_mobile_app_track_event();
+        let result = SnapchatComponent::track(event, sample_settings_with_mobile_app());
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let event = &body["data"][0];
+        assert_eq!(event["action_source"], "MOBILE_APP");
+        assert_eq!(
+            event["user_data"]["madid"],
+            "6d92078a-8246-4ba4-ae5b-76104861e7dc"
+        );
+        assert_eq!(event["app_data"]["app_id"], "com.example.app");
+        assert_eq!(event["app_data"]["advertiser_tracking_enabled"], true);
+        assert_eq!(
+            event["app_data"]["extinfo"],
+            serde_json::json!([
+                "i2",
+                "com.example.app",
+                "",
+                "",
+                "17.4",
+                "iPhone15,2",
+                "fr_FR",
+                "CET",
+                "",
+                "1024",
+                "768",
+                "2.00",
+                "",
+                "",
+                "",
+                "CET"
+            ])
+        );
+    }
+
+    #[test]
+    fn track_mobile_app_event_with_zeroed_madid() {
+        let mut event = sample_mobile_app_track_event();
+        event.context.client.os_name = "Android".to_string();
+        event.context.user.properties = vec![
+            (
+                "gaid".to_string(),
+                "00000000-0000-0000-0000-000000000000".to_string(),
+            ),
+            ("email".to_string(), "test@test.com".to_string()),
+        ];
+        let result = SnapchatComponent::track(event, sample_settings_with_mobile_app());
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let event = &body["data"][0];
+        assert!(event["user_data"].get("madid").is_none());
+        assert_eq!(event["app_data"]["advertiser_tracking_enabled"], false);
+        assert_eq!(event["app_data"]["extinfo"][0], "a2");
+    }
+
+    #[test]
+    fn track_mobile_app_event_without_identifier_fails() {
+        let mut event = sample_mobile_app_track_event();
+        event.context.user.properties = vec![(
+            "advertiser_tracking_enabled".to_string(),
+            "false".to_string(),
+        )];
+        let result = SnapchatComponent::track(event, sample_settings_with_mobile_app());
+        assert_eq!(
+            result.err().unwrap().starts_with("missing_identifier: "),
+            true
+        );
+    }
+
+    #[test]
+    fn track_web_event_ignores_madid() {
+        let event = sample_mobile_app_track_event();
+        let result = SnapchatComponent::track(event, sample_settings());
+        assert_eq!(
+            result.err().unwrap().starts_with("missing_identifier: "),
+            true
+        );
+
+        let event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let result = SnapchatComponent::track(event, sample_settings());
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert!(body["data"][0].get("app_data").is_none());
+    }
+
+    #[test]
+    fn track_with_empty_name_fails() {
+        let event = sample_track_event(
+            "".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[test]
+    fn user_event() {
+        let event = sample_user_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), true);
+        assert_eq!(
+            result
+                .clone()
+                .err()
+                .unwrap()
+                .to_string()
+                .contains("User events are disabled (snapchat_user_event_name is not set)"),
+            true
+        );
+    }
+
+    #[test]
+    fn user_even_with_test_event_code() {
+        let event = sample_user_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings_with_test_code();
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), true);
+        assert_eq!(
+            result
+                .clone()
+                .err()
+                .unwrap()
+                .to_string()
+                .contains("User events are disabled (snapchat_user_event_name is not set)"),
+            true
+        );
+    }
+
+    fn sample_settings_with_user_event() -> Vec<(String, String)> {
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_user_event_name".to_string(),
+            "sign_up".to_string(),
+        ));
+        settings
+    }
+
+    #[test]
+    fn user_event_enabled() {
+        let event = sample_user_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings_with_user_event();
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), false);
+        let edgee_request = result.unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Post);
+        let body: serde_json::Value = serde_json::from_str(&edgee_request.body).unwrap();
+        assert_eq!(body["data"][0]["event_name"], "SIGN_UP");
+        assert_eq!(
+            body["data"][0]["user_data"]["em"],
+            snapchat_payload::hash_value("test@test.com")
+        );
+    }
+
+    #[test]
+    fn user_event_enabled_with_custom_event_name() {
+        let event = sample_user_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_user_event_name".to_string(),
+            "identify".to_string(),
+        ));
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["event_name"], "identify");
+    }
+
+    #[test]
+    fn user_event_enabled_with_unmapped_event_policy() {
+        let event = sample_user_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "snapchat_user_event_name".to_string(),
+            "identify".to_string(),
+        ));
+        settings.push((
+            "snapchat_unmapped_event_policy".to_string(),
+            "drop".to_string(),
+        ));
+        settings.push((
+            "snapchat_event_mapping".to_string(),
+            "identify:SIGN_UP".to_string(),
+        ));
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), false);
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["data"][0]["event_name"], "identify");
+    }
+
+    #[test]
+    fn user_event_enabled_without_properties_fails() {
+        let event = sample_user_event_without_ids(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings_with_user_event();
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), true);
+        assert_eq!(
+            result.err().unwrap().contains("User properties are empty"),
+            true
+        );
+    }
+
+    #[test]
+    fn user_event_enabled_without_consent_fails() {
+        let event = sample_user_event(
+            Some(Consent::Denied),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings_with_user_event();
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), true);
+        assert_eq!(
+            result.err().unwrap().contains("Consent is not granted"),
+            true
+        );
+    }
+
+    #[test]
+    fn user_event_without_ids_fails() {
+        let event = sample_user_event_without_ids(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), true);
+        assert_eq!(
+            result
+                .clone()
+                .err()
+                .unwrap()
+                .to_string()
+                .contains("User events are disabled (snapchat_user_event_name is not set)"),
+            true
+        );
+    }
+
+    #[test]
+    fn user_event_without_email_or_phone_fails() {
+        let event = sample_user_event_without_email(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = SnapchatComponent::user(event, settings);
+
+        assert_eq!(result.clone().is_err(), true);
+        assert_eq!(
+            result
+                .clone()
+                .err()
+                .unwrap()
+                .to_string()
+                .contains("User events are disabled (snapchat_user_event_name is not set)"),
+            true
+        );
+    }
+
+    #[test]
+    fn track_event_without_user_context_properties_fails() {
+        let mut event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            true,
+        );
+        event.context.user.properties = vec![]; // empty context user properties
+        event.context.user.user_id = "".to_string(); // empty context user id
+        let settings = sample_settings();
+        let result = SnapchatComponent::track(event, settings);
+        assert_eq!(result.clone().is_err(), true);
+        assert_eq!(
+            result
+                .clone()
+                .err()
+                .unwrap()
+                .to_string()
+                .contains("User properties are empty"),
+            true
+        );
+        assert_eq!(
+            result.err().unwrap().starts_with("missing_identifier: "),
             true
         );
     }
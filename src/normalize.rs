@@ -0,0 +1,788 @@
+/// Normalize email
+///
+/// Snapchat expects emails to be trimmed and lowercased before hashing.
+/// Returns `None` when the value doesn't look like an email address, so we never hash garbage.
+pub(crate) fn normalize_email(value: &str) -> Option<String> {
+    let email = value.trim().to_lowercase();
+
+    let (local, domain) = email.split_once('@')?;
+    if local.is_empty() || domain.contains('@') {
+        return None;
+    }
+    if email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return None;
+    }
+
+    // the domain must have at least two non-empty labels, and a top level domain of 2+ characters
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2
+        || labels
+            .iter()
+            .any(|label| label.is_empty() || label.starts_with('-') || label.ends_with('-'))
+        || labels.last().map(|tld| tld.len() < 2).unwrap_or(true)
+    {
+        return None;
+    }
+
+    Some(email)
+}
+
+/// Normalize phone number
+///
+/// Snapchat expects phone numbers in E.164 format, digits only (no `+`, spaces or punctuation).
+/// Numbers without an international prefix (`+` or `00`) are considered national numbers:
+/// the trunk prefix is removed and the calling code of `country` (ISO 3166 alpha-2) is prepended.
+pub(crate) fn normalize_phone(value: &str, country: Option<&str>) -> Option<String> {
+    let value = value.trim();
+    let mut digits: String = value.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    if value.starts_with('+') {
+        // already international
+    } else if let Some(international) = digits.strip_prefix("00") {
+        digits = international.to_string();
+    } else {
+        let country = country.map(|c| c.trim().to_uppercase())?;
+        let calling_code = calling_code(&country)?;
+        if calling_code == "1" && digits.len() == 11 && digits.starts_with('1') {
+            // North American numbers are often written with their calling code, without `+`
+        } else {
+            digits = format!("{}{}", calling_code, strip_trunk_prefix(&country, &digits));
+        }
+    }
+
+    // E.164 numbers are at most 15 digits long, and can't start with 0
+    if digits.len() < 8 || digits.len() > 15 || digits.starts_with('0') {
+        return None;
+    }
+
+    Some(digits)
+}
+
+/// Remove the trunk prefix of a national number, dialed before it within the country.
+/// Most countries use `0`, some former USSR countries use `8` (followed by a fixed-length
+/// national number), Hungary uses `06`, and in Italy the leading zero is part of the number.
+fn strip_trunk_prefix<'a>(country: &str, digits: &'a str) -> &'a str {
+    let strip_eight = |national_length: usize| match digits.strip_prefix('8') {
+        Some(national) if digits.len() > national_length => national.trim_start_matches('0'),
+        _ => digits.trim_start_matches('0'),
+    };
+    match country {
+        "IT" | "SM" | "VA" => digits,
+        "RU" | "KZ" => strip_eight(10),
+        "BY" | "TJ" => strip_eight(9),
+        "LT" | "TM" => strip_eight(8),
+        "HU" => digits
+            .strip_prefix("06")
+            .unwrap_or_else(|| digits.trim_start_matches('0')),
+        _ => digits.trim_start_matches('0'),
+    }
+}
+
+/// Returns the international calling code of a country (ISO 3166 alpha-2).
+/// Only uninhabited territories without a calling code (`BV`, `HM`) are unsupported.
+pub(crate) fn calling_code(country: &str) -> Option<&'static str> {
+    let code = match country.to_uppercase().as_str() {
+        "US" | "CA" | "PR" | "AG" | "AI" | "AS" | "BB" | "BM" | "BS" | "DM" | "DO" | "GD"
+        | "GU" | "JM" | "KN" | "KY" | "LC" | "MP" | "MS" | "SX" | "TC" | "TT" | "UM" | "VC"
+        | "VG" | "VI" => "1",
+        "RU" | "KZ" => "7",
+        "EG" => "20",
+        "ZA" => "27",
+        "GR" => "30",
+        "NL" => "31",
+        "BE" => "32",
+        "FR" => "33",
+        "ES" => "34",
+        "HU" => "36",
+        "IT" | "VA" => "39",
+        "RO" => "40",
+        "CH" => "41",
+        "AT" => "43",
+        "GB" | "GG" | "IM" | "JE" => "44",
+        "DK" => "45",
+        "SE" => "46",
+        "NO" | "SJ" => "47",
+        "PL" => "48",
+        "DE" => "49",
+        "PE" => "51",
+        "MX" => "52",
+        "CU" => "53",
+        "AR" => "54",
+        "BR" => "55",
+        "CL" => "56",
+        "CO" => "57",
+        "VE" => "58",
+        "MY" => "60",
+        "AU" | "CC" | "CX" => "61",
+        "ID" => "62",
+        "PH" => "63",
+        "NZ" | "PN" => "64",
+        "SG" => "65",
+        "TH" => "66",
+        "JP" => "81",
+        "KR" => "82",
+        "VN" => "84",
+        "CN" => "86",
+        "TR" => "90",
+        "IN" => "91",
+        "PK" => "92",
+        "AF" => "93",
+        "LK" => "94",
+        "MM" => "95",
+        "IR" => "98",
+        "SS" => "211",
+        "MA" | "EH" => "212",
+        "DZ" => "213",
+        "TN" => "216",
+        "LY" => "218",
+        "GM" => "220",
+        "SN" => "221",
+        "MR" => "222",
+        "ML" => "223",
+        "GN" => "224",
+        "CI" => "225",
+        "BF" => "226",
+        "NE" => "227",
+        "TG" => "228",
+        "BJ" => "229",
+        "MU" => "230",
+        "LR" => "231",
+        "SL" => "232",
+        "GH" => "233",
+        "NG" => "234",
+        "TD" => "235",
+        "CF" => "236",
+        "CM" => "237",
+        "CV" => "238",
+        "ST" => "239",
+        "GQ" => "240",
+        "GA" => "241",
+        "CG" => "242",
+        "CD" => "243",
+        "AO" => "244",
+        "GW" => "245",
+        "IO" => "246",
+        "SC" => "248",
+        "SD" => "249",
+        "RW" => "250",
+        "ET" => "251",
+        "SO" => "252",
+        "DJ" => "253",
+        "KE" => "254",
+        "TZ" => "255",
+        "UG" => "256",
+        "BI" => "257",
+        "MZ" => "258",
+        "ZM" => "260",
+        "MG" => "261",
+        "RE" | "TF" | "YT" => "262",
+        "ZW" => "263",
+        "NA" => "264",
+        "MW" => "265",
+        "LS" => "266",
+        "BW" => "267",
+        "SZ" => "268",
+        "KM" => "269",
+        "SH" => "290",
+        "ER" => "291",
+        "AW" => "297",
+        "FO" => "298",
+        "GL" => "299",
+        "GI" => "350",
+        "PT" => "351",
+        "LU" => "352",
+        "IE" => "353",
+        "IS" => "354",
+        "AL" => "355",
+        "MT" => "356",
+        "CY" => "357",
+        "FI" | "AX" => "358",
+        "BG" => "359",
+        "LT" => "370",
+        "LV" => "371",
+        "EE" => "372",
+        "MD" => "373",
+        "AM" => "374",
+        "BY" => "375",
+        "AD" => "376",
+        "MC" => "377",
+        "SM" => "378",
+        "UA" => "380",
+        "RS" => "381",
+        "ME" => "382",
+        "HR" => "385",
+        "SI" => "386",
+        "BA" => "387",
+        "MK" => "389",
+        "CZ" => "420",
+        "SK" => "421",
+        "LI" => "423",
+        "FK" | "GS" => "500",
+        "BZ" => "501",
+        "GT" => "502",
+        "SV" => "503",
+        "HN" => "504",
+        "NI" => "505",
+        "CR" => "506",
+        "PA" => "507",
+        "PM" => "508",
+        "HT" => "509",
+        "GP" | "BL" | "MF" => "590",
+        "BO" => "591",
+        "GY" => "592",
+        "EC" => "593",
+        "GF" => "594",
+        "PY" => "595",
+        "MQ" => "596",
+        "SR" => "597",
+        "UY" => "598",
+        "CW" | "BQ" => "599",
+        "TL" => "670",
+        "NF" | "AQ" => "672",
+        "BN" => "673",
+        "NR" => "674",
+        "PG" => "675",
+        "TO" => "676",
+        "SB" => "677",
+        "VU" => "678",
+        "FJ" => "679",
+        "PW" => "680",
+        "WF" => "681",
+        "CK" => "682",
+        "NU" => "683",
+        "WS" => "685",
+        "KI" => "686",
+        "NC" => "687",
+        "TV" => "688",
+        "PF" => "689",
+        "TK" => "690",
+        "FM" => "691",
+        "MH" => "692",
+        "KP" => "850",
+        "HK" => "852",
+        "MO" => "853",
+        "KH" => "855",
+        "LA" => "856",
+        "BD" => "880",
+        "TW" => "886",
+        "MV" => "960",
+        "LB" => "961",
+        "JO" => "962",
+        "SY" => "963",
+        "IQ" => "964",
+        "KW" => "965",
+        "SA" => "966",
+        "YE" => "967",
+        "OM" => "968",
+        "PS" => "970",
+        "AE" => "971",
+        "IL" => "972",
+        "BH" => "973",
+        "QA" => "974",
+        "BT" => "975",
+        "MN" => "976",
+        "NP" => "977",
+        "TJ" => "992",
+        "TM" => "993",
+        "AZ" => "994",
+        "GE" => "995",
+        "KG" => "996",
+        "UZ" => "998",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Normalize first or last name
+///
+/// Snapchat expects names lowercased, without punctuation or spaces (UTF-8 letters are kept).
+pub(crate) fn normalize_name(value: &str) -> Option<String> {
+    non_empty(letters_only(value))
+}
+
+/// Normalize gender
+///
+/// Snapchat expects `m` or `f`.
+pub(crate) fn normalize_gender(value: &str) -> Option<String> {
+    match letters_only(value).as_str() {
+        "m" | "male" | "man" => Some("m".to_string()),
+        "f" | "female" | "woman" => Some("f".to_string()),
+        _ => None,
+    }
+}
+
+/// Normalize date of birth
+///
+/// Snapchat expects birth dates as `YYYYMMDD`.
+/// Accepted inputs are `YYYYMMDD`, `YYYY-MM-DD`, `YYYY/MM/DD` and `YYYY.MM.DD`, optionally followed by a time (`1979-12-31T00:00:00Z`).
+pub(crate) fn normalize_date_of_birth(value: &str) -> Option<String> {
+    let value = value.trim();
+    let date = value.split(['T', ' ']).next().unwrap_or(value);
+
+    let parts: Vec<&str> = date.split(['-', '/', '.']).collect();
+    let (year, month, day) = match parts.as_slice() {
+        [date] if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) => {
+            (&date[0..4], &date[4..6], &date[6..8])
+        }
+        [year, month, day] if year.len() == 4 && month.len() <= 2 && day.len() <= 2 => {
+            (*year, *month, *day)
+        }
+        _ => return None,
+    };
+
+    let year: u32 = year.parse().ok()?;
+    let month: u32 = month.parse().ok()?;
+    let day: u32 = day.parse().ok()?;
+
+    let leap_year =
+        (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap_year => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if !(1900..=2100).contains(&year) || day == 0 || day > days_in_month {
+        return None;
+    }
+
+    Some(format!("{:04}{:02}{:02}", year, month, day))
+}
+
+/// Normalize city
+///
+/// Snapchat expects cities lowercased, without punctuation or spaces.
+pub(crate) fn normalize_city(value: &str) -> Option<String> {
+    non_empty(letters_only(value))
+}
+
+/// Normalize state
+///
+/// Snapchat expects two-letter state codes, lowercased.
+/// US state names are converted to their code; other values are lowercased without punctuation or spaces.
+pub(crate) fn normalize_state(value: &str) -> Option<String> {
+    let state = letters_only(value);
+    if state.len() == 2 {
+        return Some(state);
+    }
+
+    match US_STATES.iter().find(|(_, name)| *name == state) {
+        Some((code, _)) => Some(code.to_string()),
+        None => non_empty(state),
+    }
+}
+
+/// Normalize zip code
+///
+/// Snapchat expects zip codes lowercased, without spaces or dashes.
+/// US zip codes are truncated to their first five digits (`12345-6789` becomes `12345`).
+pub(crate) fn normalize_zip_code(value: &str) -> Option<String> {
+    let zip_code: String = value
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+
+    if zip_code.len() == 9 && zip_code.chars().all(|c| c.is_ascii_digit()) {
+        return Some(zip_code[0..5].to_string());
+    }
+
+    non_empty(zip_code)
+}
+
+/// Normalize country
+///
+/// Snapchat expects lowercase ISO 3166 alpha-2 country codes.
+/// Common alpha-3 codes and country names are converted to their alpha-2 code.
+pub(crate) fn normalize_country(value: &str) -> Option<String> {
+    let country = letters_only(value);
+    if let Some((code, _)) = COUNTRIES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&country.as_str()))
+    {
+        return Some(code.to_string());
+    }
+
+    if country.len() == 2 && country.is_ascii() {
+        Some(country)
+    } else {
+        None
+    }
+}
+
+/// Lowercase the value, and keep letters only.
+fn letters_only(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// US states and territories, with their name lowercased without spaces.
+pub(crate) const US_STATES: &[(&str, &str)] = &[
+    ("al", "alabama"),
+    ("ak", "alaska"),
+    ("as", "americansamoa"),
+    ("az", "arizona"),
+    ("ar", "arkansas"),
+    ("ca", "california"),
+    ("co", "colorado"),
+    ("ct", "connecticut"),
+    ("de", "delaware"),
+    ("dc", "districtofcolumbia"),
+    ("fl", "florida"),
+    ("ga", "georgia"),
+    ("gu", "guam"),
+    ("hi", "hawaii"),
+    ("id", "idaho"),
+    ("il", "illinois"),
+    ("in", "indiana"),
+    ("ia", "iowa"),
+    ("ks", "kansas"),
+    ("ky", "kentucky"),
+    ("la", "louisiana"),
+    ("me", "maine"),
+    ("md", "maryland"),
+    ("ma", "massachusetts"),
+    ("mi", "michigan"),
+    ("mn", "minnesota"),
+    ("ms", "mississippi"),
+    ("mo", "missouri"),
+    ("mt", "montana"),
+    ("ne", "nebraska"),
+    ("nv", "nevada"),
+    ("nh", "newhampshire"),
+    ("nj", "newjersey"),
+    ("nm", "newmexico"),
+    ("ny", "newyork"),
+    ("nc", "northcarolina"),
+    ("nd", "northdakota"),
+    ("mp", "northernmarianaislands"),
+    ("oh", "ohio"),
+    ("ok", "oklahoma"),
+    ("or", "oregon"),
+    ("pa", "pennsylvania"),
+    ("pr", "puertorico"),
+    ("ri", "rhodeisland"),
+    ("sc", "southcarolina"),
+    ("sd", "southdakota"),
+    ("tn", "tennessee"),
+    ("tx", "texas"),
+    ("ut", "utah"),
+    ("vt", "vermont"),
+    ("vi", "virginislands"),
+    ("va", "virginia"),
+    ("wa", "washington"),
+    ("wv", "westvirginia"),
+    ("wi", "wisconsin"),
+    ("wy", "wyoming"),
+];
+
+/// Countries, with their alpha-3 code and common names lowercased without spaces.
+const COUNTRIES: &[(&str, &[&str])] = &[
+    ("ae", &["are", "unitedarabemirates", "uae"]),
+    ("ar", &["arg", "argentina"]),
+    ("at", &["aut", "austria"]),
+    ("au", &["aus", "australia"]),
+    ("be", &["bel", "belgium"]),
+    ("br", &["bra", "brazil", "brasil"]),
+    ("ca", &["can", "canada"]),
+    ("ch", &["che", "switzerland"]),
+    ("cn", &["chn", "china"]),
+    ("de", &["deu", "germany", "deutschland"]),
+    ("dk", &["dnk", "denmark"]),
+    ("es", &["esp", "spain", "españa"]),
+    ("fi", &["fin", "finland"]),
+    ("fr", &["fra", "france"]),
+    ("gb", &["gbr", "uk", "unitedkingdom", "greatbritain"]),
+    ("ie", &["irl", "ireland"]),
+    ("il", &["isr", "israel"]),
+    ("in", &["ind", "india"]),
+    ("it", &["ita", "italy", "italia"]),
+    ("jp", &["jpn", "japan"]),
+    ("kr", &["kor", "southkorea", "korea"]),
+    ("mx", &["mex", "mexico", "méxico"]),
+    ("nl", &["nld", "netherlands", "thenetherlands"]),
+    ("no", &["nor", "norway"]),
+    ("nz", &["nzl", "newzealand"]),
+    ("pl", &["pol", "poland"]),
+    ("pt", &["prt", "portugal"]),
+    ("ru", &["rus", "russia"]),
+    ("sa", &["sau", "saudiarabia"]),
+    ("se", &["swe", "sweden"]),
+    ("sg", &["sgp", "singapore"]),
+    ("tr", &["tur", "turkey", "türkiye"]),
+    ("ua", &["ukr", "ukraine"]),
+    (
+        "us",
+        &["usa", "unitedstates", "unitedstatesofamerica", "america"],
+    ),
+    ("za", &["zaf", "southafrica"]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapchat_payload::hash_value;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn normalize_email_table() {
+        let cases = [
+            ("john.doe@example.com", Some("john.doe@example.com")),
+            (" John.Doe@Example.com ", Some("john.doe@example.com")),
+            ("\tJOHN.DOE@EXAMPLE.COM\n", Some("john.doe@example.com")),
+            (
+                "john+snap@mail.example.co.uk",
+                Some("john+snap@mail.example.co.uk"),
+            ),
+            ("", None),
+            ("   ", None),
+            ("john.doe", None),
+            ("john.doe@", None),
+            ("@example.com", None),
+            ("john@doe@example.com", None),
+            ("john doe@example.com", None),
+            ("john.doe@example", None),
+            ("john.doe@example.c", None),
+            ("john.doe@.example.com", None),
+            ("john.doe@example..com", None),
+            ("john.doe@-example.com", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_email(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_phone_table() {
+        let cases = [
+            ("+39 1231231231", None, Some("391231231231")),
+            ("+1 (555) 123-4567", None, Some("15551234567")),
+            ("+44 20 7946 0958", Some("FR"), Some("442079460958")),
+            ("0033 6 12 34 56 78", None, Some("33612345678")),
+            ("06 12 34 56 78", Some("FR"), Some("33612345678")),
+            ("06.12.34.56.78", Some("fr"), Some("33612345678")),
+            ("020 7946 0958", Some("GB"), Some("442079460958")),
+            ("(555) 123-4567", Some("US"), Some("15551234567")),
+            ("1-555-123-4567", Some("US"), Some("15551234567")),
+            ("06 1234 5678", Some("IT"), Some("390612345678")),
+            ("8 912 345-67-89", Some("RU"), Some("79123456789")),
+            ("912 345-67-89", Some("RU"), Some("79123456789")),
+            ("812 345-67-89", Some("RU"), Some("78123456789")),
+            ("8 701 234 5678", Some("KZ"), Some("77012345678")),
+            ("8 029 123-45-67", Some("BY"), Some("375291234567")),
+            ("8 612 34567", Some("LT"), Some("37061234567")),
+            ("0 612 34567", Some("LT"), Some("37061234567")),
+            ("06 20 123 4567", Some("HU"), Some("36201234567")),
+            ("5123 4567", Some("EE"), Some("37251234567")),
+            ("06 12 34 56 78", Some("MC"), Some("377612345678")),
+            ("06 12 34 56 78", None, None),
+            ("06 12 34 56 78", Some("XX"), None),
+            ("+33 12", None, None),
+            ("+1234567890123456", None, None),
+            ("not a phone", Some("FR"), None),
+            ("", Some("FR"), None),
+        ];
+
+        for (input, country, expected) in cases {
+            assert_eq!(
+                normalize_phone(input, country),
+                expected.map(String::from),
+                "input: {:?} ({:?})",
+                input,
+                country
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_name_table() {
+        let cases = [
+            ("John", Some("john")),
+            ("  DOE ", Some("doe")),
+            ("O'Brien", Some("obrien")),
+            ("Mary-Ann", Some("maryann")),
+            ("Jean Luc", Some("jeanluc")),
+            ("Élodie", Some("élodie")),
+            ("J.", Some("j")),
+            ("", None),
+            ("123", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_name(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_gender_table() {
+        let cases = [
+            ("Male", Some("m")),
+            ("m", Some("m")),
+            (" MAN ", Some("m")),
+            ("Female", Some("f")),
+            ("F", Some("f")),
+            ("woman", Some("f")),
+            ("other", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_gender(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_date_of_birth_table() {
+        let cases = [
+            ("1979-12-31", Some("19791231")),
+            ("19791231", Some("19791231")),
+            ("1979/12/31", Some("19791231")),
+            ("1979.1.5", Some("19790105")),
+            (" 1979-12-31T00:00:00Z ", Some("19791231")),
+            ("1979-12-31 10:00", Some("19791231")),
+            ("2000-02-29", Some("20000229")),
+            ("1900-02-29", None),
+            ("1979-02-30", None),
+            ("1979-13-01", None),
+            ("1979-00-10", None),
+            ("31/12/1979", None),
+            ("12311979", None),
+            ("yesterday", None),
+            ("aéééa", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_date_of_birth(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_city_table() {
+        let cases = [
+            ("Las Vegas", Some("lasvegas")),
+            ("Paris", Some("paris")),
+            ("Saint-Étienne", Some("saintétienne")),
+            ("St. Louis", Some("stlouis")),
+            ("", None),
+            ("   ", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_city(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_state_table() {
+        let cases = [
+            ("Nevada", Some("nv")),
+            ("NV", Some("nv")),
+            (" ca ", Some("ca")),
+            ("New York", Some("ny")),
+            ("District of Columbia", Some("dc")),
+            ("Île-de-France", Some("îledefrance")),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_state(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_zip_code_table() {
+        let cases = [
+            ("11111", Some("11111")),
+            ("94107-1234", Some("94107")),
+            ("941071234", Some("94107")),
+            ("SW1A 1AA", Some("sw1a1aa")),
+            ("75001", Some("75001")),
+            ("1234-567", Some("1234567")),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_zip_code(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_country_table() {
+        let cases = [
+            ("USA", Some("us")),
+            ("US", Some("us")),
+            ("United States", Some("us")),
+            ("fr", Some("fr")),
+            ("FRA", Some("fr")),
+            ("France", Some("fr")),
+            ("UK", Some("gb")),
+            ("United Kingdom", Some("gb")),
+            ("Atlantis", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_country(input),
+                expected.map(String::from),
+                "input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn normalized_email_hash() {
+        let expected = "836f82db99121b3481011f16b49dfa5fbc714a0d1b1b9f784a1ebbbf5b39577f";
+        for input in [
+            "john.doe@example.com",
+            " John.Doe@Example.com ",
+            "JOHN.DOE@EXAMPLE.COM",
+        ] {
+            assert_eq!(hash_value(&normalize_email(input).unwrap()), expected);
+        }
+    }
+}
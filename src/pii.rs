@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::SnapchatError;
+use crate::normalize::{normalize_email, normalize_phone};
+use crate::snapchat_payload::hash_value;
+use crate::user_property_aliases::UserPropertyAliases;
+
+/// Value replacing personal data with the `redact` policy.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// User data fields whose values are personal data, when found as custom data keys.
+/// Only the exact field names and the configured user property aliases are matched,
+/// as the built-in aliases (e.g. `phone`, `mobile`) are common non-personal property names.
+const PII_FIELDS: &[&str] = &[
+    "email",
+    "phone_number",
+    "first_name",
+    "last_name",
+    "date_of_birth",
+];
+
+/// PII policy
+///
+/// Decides what happens to personal data found in the custom data:
+/// - `hash`: the personal data is normalized and hashed with SHA-256
+/// - `redact`: the personal data is replaced with `[REDACTED]`
+/// - `drop`: the property is removed from the custom data
+/// - `off`: the custom data is not scanned
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) enum PiiPolicy {
+    Hash,
+    #[default]
+    Redact,
+    Drop,
+    Off,
+}
+
+impl PiiPolicy {
+    pub fn parse(value: &str) -> Result<Self, SnapchatError> {
+        match value.trim().to_lowercase().as_str() {
+            "hash" => Ok(PiiPolicy::Hash),
+            "redact" => Ok(PiiPolicy::Redact),
+            "drop" => Ok(PiiPolicy::Drop),
+            "off" => Ok(PiiPolicy::Off),
+            _ => Err(SnapchatError::invalid_setting(
+                "snapchat_pii_policy",
+                format!("{} (expected hash, redact, drop or off)", value),
+            )),
+        }
+    }
+}
+
+/// How personal data was detected in a custom data property.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PiiDetection {
+    /// The property name is a personal data field (e.g. `email`).
+    Key,
+    /// The value contains an email address.
+    Email,
+    /// The value is a phone number.
+    Phone,
+}
+
+/// PII audit record
+///
+/// What the scrubber did to a custom data property. It never holds the personal data itself.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PiiAudit {
+    pub key: String,
+    pub detection: PiiDetection,
+    pub policy: PiiPolicy,
+}
+
+impl fmt::Display for PiiAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let detection = match self.detection {
+            PiiDetection::Key => "personal data property",
+            PiiDetection::Email => "email address",
+            PiiDetection::Phone => "phone number",
+        };
+        let action = match self.policy {
+            PiiPolicy::Hash => "value hashed",
+            PiiPolicy::Redact => "value redacted",
+            PiiPolicy::Drop => "property dropped",
+            PiiPolicy::Off => "nothing done",
+        };
+        write!(
+            f,
+            "custom_data property {}: {} detected, {}",
+            self.key, detection, action
+        )
+    }
+}
+
+/// Scrub custom data
+///
+/// Scans the custom data for personal data (properties named like a personal data field,
+/// email addresses and phone numbers), and applies the PII policy to it.
+/// Returns an audit record for every scrubbed property, sorted by property name.
+pub(crate) fn scrub_custom_data(
+    custom_data: &mut HashMap<String, serde_json::Value>,
+    policy: PiiPolicy,
+    aliases: &UserPropertyAliases,
+) -> Vec<PiiAudit> {
+    let mut audit = vec![];
+    if policy == PiiPolicy::Off {
+        return audit;
+    }
+
+    let mut keys: Vec<String> = custom_data.keys().cloned().collect();
+    keys.sort();
+    for key in keys {
+        let is_pii_key = PII_FIELDS.contains(&key.as_str())
+            || aliases
+                .resolve_configured(&key)
+                .is_some_and(|field| PII_FIELDS.contains(&field));
+        let detection = if is_pii_key {
+            Some(PiiDetection::Key)
+        } else {
+            detect(&custom_data[&key])
+        };
+        let Some(detection) = detection else {
+            continue;
+        };
+
+        if policy == PiiPolicy::Drop {
+            custom_data.remove(&key);
+        } else if let Some(value) = custom_data.get_mut(&key) {
+            if detection == PiiDetection::Key {
+                replace_all(value, policy);
+            } else {
+                replace_pii(value, policy);
+            }
+        }
+        audit.push(PiiAudit {
+            key,
+            detection,
+            policy,
+        });
+    }
+    audit
+}
+
+/// Returns how personal data was detected in a value (or one of its items), if any.
+fn detect(value: &serde_json::Value) -> Option<PiiDetection> {
+    match value {
+        serde_json::Value::String(text) => {
+            if is_phone(text) {
+                Some(PiiDetection::Phone)
+            } else if email_tokens(text).next().is_some() {
+                Some(PiiDetection::Email)
+            } else {
+                None
+            }
+        }
+        serde_json::Value::Array(items) => items.iter().find_map(detect),
+        serde_json::Value::Object(map) => map.values().find_map(detect),
+        _ => None,
+    }
+}
+
+/// Replace every value (or item) with its hash or `[REDACTED]`.
+fn replace_all(value: &mut serde_json::Value, policy: PiiPolicy) {
+    match value {
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|v| replace_all(v, policy)),
+        serde_json::Value::Object(map) => map.values_mut().for_each(|v| replace_all(v, policy)),
+        serde_json::Value::Null => {}
+        serde_json::Value::String(text) => *text = replacement(text, policy),
+        other => *other = replacement(&other.to_string(), policy).into(),
+    }
+}
+
+/// Replace the phone numbers and email addresses in a value (or its items)
+/// with their hash or `[REDACTED]`. The rest of the text is kept.
+fn replace_pii(value: &mut serde_json::Value, policy: PiiPolicy) {
+    match value {
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|v| replace_pii(v, policy)),
+        serde_json::Value::Object(map) => map.values_mut().for_each(|v| replace_pii(v, policy)),
+        serde_json::Value::String(text) if is_phone(text) => {
+            *text = replacement(text, policy);
+        }
+        serde_json::Value::String(text) => {
+            let emails: Vec<String> = email_tokens(text).map(str::to_string).collect();
+            for email in emails {
+                *text = text.replace(&email, &replacement(&email, policy));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Hash of the normalized value with the `hash` policy, `[REDACTED]` otherwise.
+fn replacement(value: &str, policy: PiiPolicy) -> String {
+    if policy != PiiPolicy::Hash {
+        return REDACTED.to_string();
+    }
+    let normalized = normalize_email(value)
+        .or_else(|| normalize_phone(value, None))
+        .unwrap_or_else(|| value.trim().to_lowercase());
+    hash_value(&normalized)
+}
+
+/// Email addresses found in a text, without their surrounding punctuation.
+fn email_tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .map(|token| {
+            token.trim_matches(|c: char| {
+                matches!(
+                    c,
+                    '<' | '>' | '(' | ')' | '[' | ']' | '"' | '\'' | ',' | ';' | '.'
+                )
+            })
+        })
+        .filter(|token| normalize_email(token).is_some())
+}
+
+/// Returns whether a text is a phone number, 9 to 15 digits long, written either:
+/// - with an international prefix (`+33 6 12 34 56 78`, `+14155552671`)
+/// - in the North American format (`(415) 555-2671`, `415-555-2671`)
+/// - in a national format with a trunk prefix, in groups of 2 to 4 digits (`06 12 34 56 78`)
+///
+/// Other shapes are not considered phone numbers, as they are usually IDs
+/// (`0612345678`, `123-456-789`, ISBNs or order numbers).
+fn is_phone(text: &str) -> bool {
+    let text = text.trim();
+    let digits = text.chars().filter(char::is_ascii_digit).count();
+    if !(9..=15).contains(&digits) {
+        return false;
+    }
+    if let Some(number) = text.strip_prefix('+') {
+        return number
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '(' | ')'));
+    }
+
+    let mut groups: Vec<&str> = text.split([' ', '-']).filter(|g| !g.is_empty()).collect();
+    if let Some(area_code) = groups
+        .first()
+        .and_then(|group| group.strip_prefix('('))
+        .and_then(|group| group.strip_suffix(')'))
+    {
+        groups[0] = area_code;
+    }
+    if !groups
+        .iter()
+        .all(|group| group.chars().all(|c| c.is_ascii_digit()))
+    {
+        return false;
+    }
+
+    let lengths: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+    let north_american = lengths == [3, 3, 4];
+    let national = groups[0].starts_with('0')
+        && groups.len() >= 3
+        && lengths.iter().all(|length| (2..=4).contains(length));
+    north_american || national
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn custom_data(value: serde_json::Value) -> HashMap<String, serde_json::Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn detect_table() {
+        let cases = [
+            (json!("john.doe@example.com"), Some(PiiDetection::Email)),
+            (
+                json!("Contact: <John.Doe@example.com>, thanks"),
+                Some(PiiDetection::Email),
+            ),
+            (json!("+33 6 12 34 56 78"), Some(PiiDetection::Phone)),
+            (json!("(415) 555-2671"), Some(PiiDetection::Phone)),
+            (json!("+33612345678"), Some(PiiDetection::Phone)),
+            (
+                json!(["sku1", "jane@example.org"]),
+                Some(PiiDetection::Email),
+            ),
+            (
+                json!({"contact": {"tel": "06 12 34 56 78"}}),
+                Some(PiiDetection::Phone),
+            ),
+            (json!("415-555-2671"), Some(PiiDetection::Phone)),
+            (json!("020 7946 0958"), Some(PiiDetection::Phone)),
+            (json!("+1 (415) 555-2671"), Some(PiiDetection::Phone)),
+            (json!("0612345678"), None),
+            (json!("978-3-16-148410-0"), None),
+            (json!("2024-000123-45"), None),
+            (json!("123-456-789"), None),
+            (json!("(415) 555-2671 ext"), None),
+            (json!("06 12 34 5 678"), None),
+            (json!("2024-01-15"), None),
+            (json!("192.168.0.1"), None),
+            (json!("12.50"), None),
+            (json!("john@localhost"), None),
+            (json!(33612345678u64), None),
+            (json!(true), None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(detect(&value), expected, "value: {}", value);
+        }
+    }
+
+    #[test]
+    fn scrub_with_redact_policy() {
+        let mut data = custom_data(json!({
+            "email": "john.doe@example.com",
+            "comment": "Sent by john.doe@example.com.",
+            "contact": "+33 6 12 34 56 78",
+            "emails": ["a@example.com", "b@example.com"],
+            "value": 12.5,
+            "order_id": "0612345678"
+        }));
+        let audit = scrub_custom_data(
+            &mut data,
+            PiiPolicy::Redact,
+            &UserPropertyAliases::default(),
+        );
+        assert_eq!(
+            data,
+            custom_data(json!({
+                "email": "[REDACTED]",
+                "comment": "Sent by [REDACTED].",
+                "contact": "[REDACTED]",
+                "emails": ["[REDACTED]", "[REDACTED]"],
+                "value": 12.5,
+                "order_id": "0612345678"
+            }))
+        );
+        assert_eq!(
+            audit
+                .iter()
+                .map(|record| record.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "custom_data property comment: email address detected, value redacted",
+                "custom_data property contact: phone number detected, value redacted",
+                "custom_data property email: personal data property detected, value redacted",
+                "custom_data property emails: email address detected, value redacted",
+            ]
+        );
+    }
+
+    #[test]
+    fn scrub_with_hash_policy() {
+        let mut data = custom_data(json!({
+            "email": " John.Doe@example.com ",
+            "contact": "+33 6 12 34 56 78",
+            "first_name": "John",
+            "page_title": "Welcome"
+        }));
+        let audit = scrub_custom_data(&mut data, PiiPolicy::Hash, &UserPropertyAliases::default());
+        assert_eq!(audit.len(), 3);
+        assert_eq!(data["email"], hash_value("john.doe@example.com"));
+        assert_eq!(data["contact"], hash_value("33612345678"));
+        assert_eq!(data["first_name"], hash_value("john"));
+        assert_eq!(data["page_title"], "Welcome");
+    }
+
+    #[test]
+    fn scrub_with_drop_policy() {
+        let mut data = custom_data(json!({
+            "phone": "06 12 34 56 78",
+            "items": [{"id": "sku1", "buyer": "jane@example.org"}],
+            "currency": "EUR"
+        }));
+        let audit = scrub_custom_data(&mut data, PiiPolicy::Drop, &UserPropertyAliases::default());
+        assert_eq!(data, custom_data(json!({"currency": "EUR"})));
+        assert_eq!(
+            audit,
+            vec![
+                PiiAudit {
+                    key: "items".to_string(),
+                    detection: PiiDetection::Email,
+                    policy: PiiPolicy::Drop,
+                },
+                PiiAudit {
+                    key: "phone".to_string(),
+                    detection: PiiDetection::Phone,
+                    policy: PiiPolicy::Drop,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn scrub_with_off_policy() {
+        let mut data = custom_data(json!({"email": "john.doe@example.com"}));
+        let audit = scrub_custom_data(&mut data, PiiPolicy::Off, &UserPropertyAliases::default());
+        assert!(audit.is_empty());
+        assert_eq!(data["email"], "john.doe@example.com");
+    }
+
+    #[test]
+    fn scrub_ignores_builtin_aliases() {
+        let mut data = custom_data(json!({
+            "mobile": true,
+            "phone": "iPhone 15",
+            "emailAddress": "not an email"
+        }));
+        let audit = scrub_custom_data(
+            &mut data,
+            PiiPolicy::Redact,
+            &UserPropertyAliases::default(),
+        );
+        assert!(audit.is_empty());
+        assert_eq!(data["mobile"], true);
+        assert_eq!(data["phone"], "iPhone 15");
+    }
+
+    #[test]
+    fn scrub_uses_user_property_aliases() {
+        let aliases = UserPropertyAliases::new(Some(&"contact_mail:email".to_string())).unwrap();
+        let mut data = custom_data(json!({"contact_mail": "not an email"}));
+        let audit = scrub_custom_data(&mut data, PiiPolicy::Redact, &aliases);
+        assert_eq!(audit.len(), 1);
+        assert_eq!(data["contact_mail"], REDACTED);
+    }
+}
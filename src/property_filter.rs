@@ -0,0 +1,148 @@
+/// Property filter
+///
+/// Decides which keys are forwarded in the Snapchat custom data (the page and track properties,
+/// as well as the page and product keys filled by the component),
+/// with comma-separated glob patterns (`*` matches any characters, `?` a single one):
+/// - the `snapchat_custom_data_allowlist` setting: only the matching properties are forwarded
+/// - the `snapchat_custom_data_denylist` setting: the matching properties are never forwarded
+///
+/// When both are set, a property must match the allowlist and not match the denylist.
+/// Patterns are case-sensitive.
+#[derive(Debug, Default)]
+pub(crate) struct PropertyFilter {
+    allowlist: Option<Vec<String>>,
+    denylist: Vec<String>,
+}
+
+impl PropertyFilter {
+    pub fn new(allowlist: Option<&String>, denylist: Option<&String>) -> Self {
+        PropertyFilter {
+            allowlist: allowlist
+                .map(|patterns| parse_patterns(patterns))
+                .filter(|patterns| !patterns.is_empty()),
+            denylist: denylist
+                .map(|patterns| parse_patterns(patterns))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns whether the property can be forwarded in the custom data.
+    pub fn allows(&self, key: &str) -> bool {
+        if let Some(ref allowlist) = self.allowlist {
+            if !allowlist.iter().any(|pattern| glob_match(pattern, key)) {
+                return false;
+            }
+        }
+        !self.denylist.iter().any(|pattern| glob_match(pattern, key))
+    }
+}
+
+/// Parse comma-separated patterns, ignoring empty ones.
+pub(crate) fn parse_patterns(patterns: &str) -> Vec<String> {
+    patterns
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Match a key against a glob pattern, where `*` matches any sequence of characters
+/// (including none) and `?` matches exactly one character.
+pub(crate) fn glob_match(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+
+    let (mut p, mut k) = (0, 0);
+    // position of the last `*` in the pattern, and of the key when it was reached
+    let mut backtrack: Option<(usize, usize)> = None;
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, k));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last `*` match one more character
+            p = star + 1;
+            k = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn glob_match_table() {
+        let cases = [
+            ("order_id", "order_id", true),
+            ("order_id", "order_ids", false),
+            ("*", "anything", true),
+            ("*", "", true),
+            ("_*", "_internal", true),
+            ("_*", "internal_", false),
+            ("*_id", "order_id", true),
+            ("*_id", "id", false),
+            ("user.*.email", "user.billing.email", true),
+            ("user.*.email", "user.email", false),
+            ("page_?", "page_1", true),
+            ("page_?", "page_10", false),
+            ("*a*b*", "xxaxxbxx", true),
+            ("*a*b*", "xxbxxaxx", false),
+            ("Email", "email", false),
+        ];
+        for (pattern, key, expected) in cases {
+            assert_eq!(
+                glob_match(pattern, key),
+                expected,
+                "pattern: {}, key: {}",
+                pattern,
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn allows_everything_by_default() {
+        let filter = PropertyFilter::default();
+        assert!(filter.allows("order_id"));
+        assert!(filter.allows("_internal"));
+    }
+
+    #[test]
+    fn allowlist_mode() {
+        let filter = PropertyFilter::new(Some(&"value, currency, content_*".to_string()), None);
+        assert!(filter.allows("value"));
+        assert!(filter.allows("content_ids"));
+        assert!(!filter.allows("email"));
+        assert!(!filter.allows("_internal"));
+    }
+
+    #[test]
+    fn denylist_mode() {
+        let filter = PropertyFilter::new(None, Some(&"_*,*email*, ,password".to_string()));
+        assert!(filter.allows("value"));
+        assert!(!filter.allows("_internal"));
+        assert!(!filter.allows("customer_email_address"));
+        assert!(!filter.allows("password"));
+    }
+
+    #[test]
+    fn denylist_applies_after_allowlist() {
+        let filter = PropertyFilter::new(
+            Some(&"content_*".to_string()),
+            Some(&"content_debug".to_string()),
+        );
+        assert!(filter.allows("content_ids"));
+        assert!(!filter.allows("content_debug"));
+        assert!(!filter.allows("value"));
+    }
+}
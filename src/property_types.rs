@@ -0,0 +1,181 @@
+use crate::error::SnapchatError;
+use crate::snapchat_payload::parse_value;
+use std::collections::HashMap;
+
+/// Property type
+///
+/// The JSON type a custom data property is converted to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PropertyType {
+    /// Booleans, numbers, arrays and objects are detected from the value.
+    Auto,
+    String,
+    Number,
+    Integer,
+    Boolean,
+}
+
+impl PropertyType {
+    pub fn parse(value: &str) -> Result<Self, SnapchatError> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Ok(PropertyType::Auto),
+            "string" => Ok(PropertyType::String),
+            "number" => Ok(PropertyType::Number),
+            "integer" => Ok(PropertyType::Integer),
+            "boolean" => Ok(PropertyType::Boolean),
+            other => Err(SnapchatError::invalid_setting(
+                "snapchat_property_types",
+                format!(
+                    "{} (expected auto, string, number, integer or boolean)",
+                    other
+                ),
+            )),
+        }
+    }
+}
+
+/// Built-in type hints
+///
+/// Snapchat custom data keys whose type is known, applied without any configuration.
+const BUILTIN_PROPERTY_TYPES: &[(&str, PropertyType)] = &[
+    ("currency", PropertyType::String),
+    ("num_items", PropertyType::Integer),
+    ("order_id", PropertyType::String),
+    ("value", PropertyType::Number),
+];
+
+/// Property types
+///
+/// Converts custom data property values to JSON, using a type hint per property key.
+/// The lookup order is:
+/// - the `snapchat_property_types` setting (e.g. `sku:string,quantity:integer`)
+/// - the built-in type hints (e.g. `order_id` -> `string`)
+///
+/// Properties without a type hint are converted with `parse_value`.
+/// Values that don't match their type hint are kept as strings.
+#[derive(Debug, Default)]
+pub(crate) struct PropertyTypes {
+    types: HashMap<String, PropertyType>,
+}
+
+impl PropertyTypes {
+    pub fn new(types: Option<&String>) -> Result<Self, SnapchatError> {
+        let mut property_types = PropertyTypes::default();
+
+        if let Some(types) = types {
+            for (key, property_type) in
+                crate::settings::parse_setting_pairs("snapchat_property_types", types)?
+            {
+                property_types
+                    .types
+                    .insert(key, PropertyType::parse(&property_type)?);
+            }
+        }
+
+        Ok(property_types)
+    }
+
+    /// Returns the type hint of a property key.
+    pub fn get(&self, key: &str) -> PropertyType {
+        if let Some(property_type) = self.types.get(key) {
+            return *property_type;
+        }
+
+        BUILTIN_PROPERTY_TYPES
+            .iter()
+            .find(|(builtin, _)| *builtin == key)
+            .map(|(_, property_type)| *property_type)
+            .unwrap_or(PropertyType::Auto)
+    }
+
+    /// Convert a property value to JSON, according to the type hint of its key.
+    pub fn parse(&self, key: &str, value: &str) -> serde_json::Value {
+        let string = || serde_json::Value::String(value.to_string());
+        match self.get(key) {
+            PropertyType::Auto => parse_value(value),
+            PropertyType::String => string(),
+            PropertyType::Number => value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(serde_json::Value::Number)
+                .unwrap_or_else(string),
+            PropertyType::Integer => parse_integer(value.trim()).unwrap_or_else(string),
+            PropertyType::Boolean => match value.trim().to_lowercase().as_str() {
+                "true" | "1" => serde_json::Value::Bool(true),
+                "false" | "0" => serde_json::Value::Bool(false),
+                _ => string(),
+            },
+        }
+    }
+}
+
+/// Parse an integer, also accepting numbers without fractional part (e.g. `3.0`).
+fn parse_integer(value: &str) -> Option<serde_json::Value> {
+    if let Ok(integer) = value.parse::<i64>() {
+        return Some(serde_json::Value::from(integer));
+    }
+    match value.parse::<f64>() {
+        Ok(number) if number.fract() == 0.0 && number.abs() < i64::MAX as f64 => {
+            Some(serde_json::Value::from(number as i64))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn builtin_type_hints() {
+        let types = PropertyTypes::default();
+        let cases = [
+            ("order_id", "000123", json!("000123")),
+            ("order_id", "true", json!("true")),
+            ("currency", "EUR", json!("EUR")),
+            ("value", "12.50", json!(12.5)),
+            ("value", "12", json!(12.0)),
+            ("value", "free", json!("free")),
+            ("num_items", "3", json!(3)),
+            ("num_items", "3.0", json!(3)),
+            ("num_items", "3.5", json!("3.5")),
+            // properties without type hint are detected from the value
+            ("quantity", "2", json!(2)),
+            ("in_stock", "true", json!(true)),
+        ];
+        for (key, value, expected) in cases {
+            assert_eq!(types.parse(key, value), expected, "{}: {}", key, value);
+        }
+    }
+
+    #[test]
+    fn settings_type_hints_take_precedence() {
+        let types = PropertyTypes::new(Some(
+            &"sku:string, zip:STRING, order_id:integer, gift:boolean, value:auto".to_string(),
+        ))
+        .unwrap();
+        let cases = [
+            ("sku", "12345678901234567890", json!("12345678901234567890")),
+            ("zip", "01234", json!("01234")),
+            ("order_id", "123", json!(123)),
+            ("gift", "1", json!(true)),
+            ("gift", "FALSE", json!(false)),
+            ("gift", "maybe", json!("maybe")),
+            ("value", "true", json!(true)),
+            ("currency", "USD", json!("USD")),
+        ];
+        for (key, value, expected) in cases {
+            assert_eq!(types.parse(key, value), expected, "{}: {}", key, value);
+        }
+    }
+
+    #[test]
+    fn invalid_settings_fail() {
+        assert!(PropertyTypes::new(Some(&"sku".to_string())).is_err());
+        assert!(PropertyTypes::new(Some(&"sku:text".to_string())).is_err());
+    }
+}
@@ -1,48 +1,31 @@
-use anyhow::anyhow;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
-use crate::exports::edgee::components::data_collection::{Consent, Data, Dict, Event};
+use crate::consent::ConsentPolicy;
+use crate::error::SnapchatError;
+use crate::event_time::{event_time_millis, now_millis};
+use crate::exports::edgee::components::data_collection::{Data, Dict, Event};
+use crate::normalize::{
+    normalize_city, normalize_country, normalize_date_of_birth, normalize_email, normalize_gender,
+    normalize_name, normalize_phone, normalize_state, normalize_zip_code,
+};
+use crate::settings::{HashPolicy, Settings};
+use crate::url::{query_param, query_string, without_query};
 
 #[derive(Serialize, Debug, Default)]
 pub(crate) struct SnapchatPayload {
     pub data: Vec<SnapchatEvent>,
     #[serde(skip)]
-    pub access_token: String,
-    #[serde(skip)]
-    pub pixel_id: String,
-    #[serde(skip)]
-    pub test_event_code: Option<String>,
+    pub settings: Settings,
 }
 
 impl SnapchatPayload {
-    pub fn new(settings: Dict) -> anyhow::Result<Self> {
-        let cred: HashMap<String, String> = settings
-            .iter()
-            .map(|(key, value)| (key.to_string(), value.to_string()))
-            .collect();
-
-        let access_token = match cred.get("snapchat_access_token") {
-            Some(key) => key,
-            None => return Err(anyhow!("Missing Snapchat Access Token")),
-        }
-        .to_string();
-
-        let pixel_id = match cred.get("snapchat_pixel_id") {
-            Some(key) => key,
-            None => return Err(anyhow!("Missing Snapchat Pixel ID")),
-        }
-        .to_string();
-
-        let test_event_code = cred.get("snapchat_test_event_code").map(|s| s.to_string());
-
-        Ok(Self {
+    pub fn new(settings: Settings) -> Self {
+        Self {
             data: vec![],
-            access_token,
-            pixel_id,
-            test_event_code,
-        })
+            settings,
+        }
     }
 }
 
@@ -58,16 +41,126 @@ impl SnapchatPayload {
 #[derive(Serialize, Debug)]
 pub struct SnapchatEvent {
     pub event_name: String,
-    pub event_time: i64,
+    pub event_time: i64, // milliseconds since the Unix epoch
     pub user_data: UserData,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub custom_data: Option<HashMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub event_source_url: Option<String>,
     pub event_id: String,
-    pub action_source: String,
+    pub action_source: ActionSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_data: Option<AppData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_processing_options: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_processing_options_country: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_processing_options_state: Option<i32>,
+    /// Restricted events are sent without any personal data (see `ConsentPolicy::Restricted`).
+    #[serde(skip)]
+    pub restricted: bool,
 }
 
+/// Data processing option flagging events for limited data use.
+pub(crate) const LIMITED_DATA_USE: &str = "LDU";
+
+/// Query string parameter carrying the Snapchat click ID on ad landing pages.
+pub(crate) const SC_CLICK_ID_PARAMETER: &str = "ScCid";
+
+/// App Data
+///
+/// Device and application information, sent with mobile app events (`MOBILE_APP` action source).
+/// `extinfo` follows the Snapchat layout: version (`i2` for iOS, `a2` for Android), package name,
+/// short version, long version, OS version, device model, locale, timezone abbreviation, carrier,
+/// screen width, screen height, screen density, CPU cores, external storage, free storage and device timezone.
+#[derive(Serialize, Debug, Default)]
+pub struct AppData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advertiser_tracking_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extinfo: Option<Vec<String>>,
+}
+
+impl AppData {
+    fn new(edgee_event: &Event, app_id: Option<String>) -> Self {
+        let client = &edgee_event.context.client;
+
+        let extinfo_version = match client.os_name.to_lowercase().as_str() {
+            "ios" | "ipados" => Some("i2"),
+            "android" => Some("a2"),
+            _ => None,
+        };
+        let positive = |value: i32| {
+            if value > 0 {
+                value.to_string()
+            } else {
+                String::new()
+            }
+        };
+
+        let extinfo = extinfo_version.map(|version| {
+            vec![
+                version.to_string(),
+                app_id.clone().unwrap_or_default(),
+                String::new(), // short version
+                String::new(), // long version
+                client.os_version.clone(),
+                client.user_agent_model.clone(),
+                client.locale.replace('-', "_"),
+                client.timezone.clone(),
+                String::new(), // carrier
+                positive(client.screen_width),
+                positive(client.screen_height),
+                if client.screen_density > 0.0 {
+                    format!("{:.2}", client.screen_density)
+                } else {
+                    String::new()
+                },
+                String::new(), // CPU cores
+                String::new(), // external storage
+                String::new(), // free storage
+                client.timezone.clone(),
+            ]
+        });
+
+        AppData {
+            app_id,
+            advertiser_tracking_enabled: None,
+            extinfo,
+        }
+    }
+}
+
+/// Action Source
+///
+/// Where the conversion happened. Snapchat CAPI accepts web, mobile app and offline events.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum ActionSource {
+    #[default]
+    Web,
+    MobileApp,
+    Offline,
+}
+
+impl ActionSource {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_uppercase().replace('-', "_").as_str() {
+            "WEB" => Some(ActionSource::Web),
+            "MOBILE_APP" | "APP" => Some(ActionSource::MobileApp),
+            "OFFLINE" => Some(ActionSource::Offline),
+            _ => None,
+        }
+    }
+}
+
+/// Reserved track property used to override the action source of a single event.
+/// It is not forwarded in the custom data.
+pub(crate) const ACTION_SOURCE_PROPERTY: &str = "action_source";
+
 // User Data
 //
 // This is the user data that will be sent to Snapchat CAPI.
@@ -105,29 +198,80 @@ pub struct UserData {
     pub sc_click_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sc_cookie1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub madid: Option<String>, // mobile advertising ID (IDFA or GAID), lowercase
 }
 
+/// Zeroed mobile advertising ID, sent by devices when ad tracking is limited.
+const ZEROED_MADID: &str = "00000000-0000-0000-0000-000000000000";
+
 impl SnapchatEvent {
-    pub fn new(edgee_event: &Event, event_name: &str) -> anyhow::Result<Self> {
+    pub fn new(
+        edgee_event: &Event,
+        event_name: &str,
+        settings: &Settings,
+    ) -> Result<Self, SnapchatError> {
+        // Check consent before validating the event, so that dropped events are always reported as such
+        let consent_policy = consent_policy(edgee_event, settings)?;
+
         // Default snapchat event
         let mut snapchat_event = SnapchatEvent {
             event_name: event_name.to_string(),
-            event_time: edgee_event.timestamp,
+            event_time: settings
+                .event_time_guard
+                .check(event_time_millis(edgee_event), now_millis())?,
             event_id: edgee_event.uuid.clone(),
             event_source_url: None,
             user_data: UserData::default(),
             custom_data: Some(HashMap::new()),
-            action_source: "WEB".to_string(),
+            action_source: settings.action_source,
+            app_data: None,
+            data_processing_options: None,
+            data_processing_options_country: None,
+            data_processing_options_state: None,
+            restricted: false,
         };
 
+        // Set action source, from the reserved track property when provided
+        if let Data::Track(ref data) = edgee_event.data {
+            if let Some((_, source)) = data
+                .properties
+                .iter()
+                .find(|(key, _)| key == ACTION_SOURCE_PROPERTY)
+            {
+                snapchat_event.action_source = ActionSource::parse(source).ok_or_else(|| {
+                    SnapchatError::InvalidEventData(format!(
+                        "Invalid {}: {} (expected WEB, MOBILE_APP or OFFLINE)",
+                        ACTION_SOURCE_PROPERTY, source
+                    ))
+                })?;
+            }
+        }
+
+        // Set event ID, from the event properties when provided, to deduplicate with the browser pixel
+        let properties = match edgee_event.data {
+            Data::Page(ref data) => data.properties.as_slice(),
+            Data::Track(ref data) => data.properties.as_slice(),
+            Data::User(_) => &[],
+        };
+        if let Some(event_id) = event_id(properties, event_name, settings) {
+            snapchat_event.event_id = event_id;
+        }
+
         // Set event source URL
-        if !edgee_event.context.page.url.is_empty() {
-            let document_location = format!(
-                "{}{}",
-                edgee_event.context.page.url.clone(),
-                edgee_event.context.page.search.clone()
-            );
-            snapchat_event.event_source_url = Some(document_location);
+        snapchat_event.event_source_url = settings.url_canonicalizer.canonicalize(
+            &edgee_event.context.page.url,
+            &edgee_event.context.page.search,
+        );
+
+        // Set data processing options, for users located where privacy laws require limited data use
+        if let Some(options) = settings.data_processing_rules.options(
+            &edgee_event.context.client.country_code,
+            &edgee_event.context.client.region,
+        ) {
+            snapchat_event.data_processing_options = Some(vec![LIMITED_DATA_USE.to_string()]);
+            snapchat_event.data_processing_options_country = Some(options.country);
+            snapchat_event.data_processing_options_state = Some(options.state);
         }
 
         // Set user data
@@ -139,7 +283,11 @@ impl SnapchatEvent {
 
         // Set user IDs
         if !edgee_event.context.user.user_id.is_empty() {
-            user_data.external_id = Some(hash_value(&edgee_event.context.user.user_id));
+            user_data.external_id = hash_user_property(
+                &edgee_event.context.user.user_id,
+                settings.hash_policy("external_id"),
+                |v| Some(v.trim().to_string()).filter(|v| !v.is_empty()),
+            );
         }
 
         let mut user_properties = edgee_event.context.user.properties.clone();
@@ -147,43 +295,144 @@ impl SnapchatEvent {
             user_properties = data.properties.clone();
         }
 
-        if edgee_event.consent.is_some() && edgee_event.consent.unwrap() != Consent::Granted {
-            // Consent is not granted, so we don't send the event
-            return Err(anyhow!("Consent is not granted"));
+        if consent_policy == ConsentPolicy::Restricted {
+            // Consent is not granted, so we send the event without any personal data
+            // (user data, query string parameters), flagged for limited data use
+            snapchat_event.restricted = true;
+            snapchat_event.event_source_url = snapchat_event
+                .event_source_url
+                .map(|url| without_query(&url).to_string());
+            snapchat_event.data_processing_options = Some(vec![LIMITED_DATA_USE.to_string()]);
+            return Ok(snapchat_event);
         }
 
         // user properties
         // You must provide at least one of the following user property.
         if user_properties.is_empty() {
-            return Err(anyhow!("User properties are empty"));
+            return Err(SnapchatError::MissingIdentifier(
+                "User properties are empty".to_string(),
+            ));
         }
 
-        // Set user properties
+        // Country used to complete phone numbers without international prefix
+        let phone_country = settings
+            .default_phone_country
+            .as_deref()
+            .or(Some(edgee_event.context.client.country_code.as_str()))
+            .filter(|country| !country.is_empty());
+
+        // Set user properties (invalid values never overwrite a valid value set by another property)
+        let mut advertiser_tracking_enabled = None;
         for (key, value) in user_properties.iter() {
-            match key.as_str() {
-                "email" => user_data.email = Some(hash_value(value)),
-                "phone_number" => user_data.phone_number = Some(hash_value(value)),
-                "first_name" => user_data.first_name = Some(hash_value(value)),
-                "last_name" => user_data.last_name = Some(hash_value(value)),
-                "gender" => user_data.gender = Some(hash_value(value)),
-                "date_of_birth" => user_data.date_of_birth = Some(hash_value(value)),
-                "city" => user_data.city = Some(hash_value(value)),
-                "state" => user_data.state = Some(hash_value(value)),
-                "zip_code" => user_data.zip_code = Some(hash_value(value)),
-                "country" => user_data.country = Some(hash_value(value)),
+            let policy = |field: &str| settings.hash_policy(field);
+            let Some(field) = settings.user_property_aliases.resolve(key) else {
+                continue;
+            };
+            match field {
+                "email" => set_user_field(
+                    &mut user_data.email,
+                    hash_user_property(value, policy("em"), normalize_email),
+                ),
+                "phone_number" => set_user_field(
+                    &mut user_data.phone_number,
+                    hash_user_property(value, policy("ph"), |v| normalize_phone(v, phone_country)),
+                ),
+                "first_name" => set_user_field(
+                    &mut user_data.first_name,
+                    hash_user_property(value, policy("fn"), normalize_name),
+                ),
+                "last_name" => set_user_field(
+                    &mut user_data.last_name,
+                    hash_user_property(value, policy("ln"), normalize_name),
+                ),
+                "gender" => set_user_field(
+                    &mut user_data.gender,
+                    hash_user_property(value, policy("ge"), normalize_gender),
+                ),
+                "date_of_birth" => set_user_field(
+                    &mut user_data.date_of_birth,
+                    hash_user_property(value, policy("db"), normalize_date_of_birth),
+                ),
+                "city" => set_user_field(
+                    &mut user_data.city,
+                    hash_user_property(value, policy("ct"), normalize_city),
+                ),
+                "state" => set_user_field(
+                    &mut user_data.state,
+                    hash_user_property(value, policy("st"), normalize_state),
+                ),
+                "zip_code" => set_user_field(
+                    &mut user_data.zip_code,
+                    hash_user_property(value, policy("zp"), normalize_zip_code),
+                ),
+                "country" => set_user_field(
+                    &mut user_data.country,
+                    hash_user_property(value, policy("country"), normalize_country),
+                ),
                 "sc_click_id" => user_data.sc_click_id = Some(value.to_string()),
                 "sc_cookie1" => user_data.sc_cookie1 = Some(value.to_string()),
+                "madid" => {
+                    let madid = value.trim().to_lowercase();
+                    if madid == ZEROED_MADID {
+                        advertiser_tracking_enabled = Some(false);
+                    } else if !madid.is_empty() {
+                        user_data.madid = Some(madid);
+                    }
+                }
+                "advertiser_tracking_enabled" => {
+                    advertiser_tracking_enabled = match value.trim().to_lowercase().as_str() {
+                        "true" | "1" => Some(true),
+                        "false" | "0" => Some(false),
+                        _ => advertiser_tracking_enabled,
+                    }
+                }
                 _ => {
                     // do nothing
                 }
             }
         }
 
-        // return error if user data doesn't have any user property
-        if user_data.email.is_none() && user_data.phone_number.is_none() {
-            return Err(anyhow!(
-                "User properties must contain email or phone_number"
-            ));
+        // Use the Snapchat click ID of the landing page URL, unless it is set in user properties
+        if user_data.sc_click_id.is_none() {
+            let page = &edgee_event.context.page;
+            user_data.sc_click_id = [
+                query_string(&page.url),
+                page.search.as_str(),
+                query_string(&page.referrer),
+            ]
+            .iter()
+            .find_map(|query| query_param(query, SC_CLICK_ID_PARAMETER));
+        }
+
+        if snapchat_event.action_source == ActionSource::MobileApp {
+            // Build app data for mobile app events
+            let mut app_data = AppData::new(edgee_event, settings.app_id.clone());
+            app_data.advertiser_tracking_enabled = advertiser_tracking_enabled;
+            if advertiser_tracking_enabled == Some(false) {
+                user_data.madid = None;
+            }
+            snapchat_event.app_data = Some(app_data);
+
+            // the mobile advertising ID also identifies the user
+            if user_data.email.is_none()
+                && user_data.phone_number.is_none()
+                && user_data.madid.is_none()
+            {
+                return Err(SnapchatError::MissingIdentifier(
+                    "User properties must contain email, phone_number or a mobile advertising ID"
+                        .to_string(),
+                ));
+            }
+        } else {
+            // mobile advertising IDs are only relevant for mobile app events
+            user_data.madid = None;
+
+            // return error if user data doesn't have any user property
+            if user_data.email.is_none() && user_data.phone_number.is_none() {
+                return Err(SnapchatError::MissingIdentifier(
+                    "User properties must contain email or phone_number".to_string(),
+                ));
+            }
         }
 
         snapchat_event.user_data = user_data;
@@ -192,23 +441,309 @@ impl SnapchatEvent {
     }
 }
 
+/// Consent policy
+///
+/// Returns the consent policy of an event, or an error when its consent doesn't allow sending it.
+/// It is checked before any other validation, so that dropped events are always reported as
+/// `consent_not_granted`.
+pub(crate) fn consent_policy(
+    edgee_event: &Event,
+    settings: &Settings,
+) -> Result<ConsentPolicy, SnapchatError> {
+    match settings.consent_mode.policy(edgee_event.consent) {
+        // Consent is not granted, so we don't send the event
+        ConsentPolicy::Drop => Err(SnapchatError::ConsentNotGranted),
+        policy => Ok(policy),
+    }
+}
+
+/// Set a user data field, unless the new value is invalid (`None`).
+fn set_user_field(field: &mut Option<String>, value: Option<String>) {
+    if value.is_some() {
+        *field = value;
+    }
+}
+
+/// Maximum length of a JSON-encoded property value.
+const MAX_JSON_VALUE_LENGTH: usize = 4096;
+
+/// Maximum nesting depth of a JSON-encoded property value.
+const MAX_JSON_VALUE_DEPTH: usize = 5;
+
+/// Property holding the order ID, used to derive deterministic event IDs.
+pub(crate) const ORDER_ID_PROPERTY: &str = "order_id";
+
+/// Event ID
+///
+/// Returns the event ID from the first non-empty property listed in `snapchat_event_id_properties`.
+/// Otherwise, when `snapchat_deterministic_event_id` is enabled and the event has an order ID,
+/// returns the SHA-256 hex digest of `<event_name>:<order_id>` (e.g. `PURCHASE:1234`).
+/// Returns `None` when the Edgee event UUID should be used.
+pub(crate) fn event_id(
+    properties: &[(String, String)],
+    event_name: &str,
+    settings: &Settings,
+) -> Option<String> {
+    let property = |name: &str| {
+        properties
+            .iter()
+            .find(|(key, value)| key == name && !value.trim().is_empty())
+            .map(|(_, value)| value.trim().to_string())
+    };
+
+    if let Some(event_id) = settings
+        .event_id_properties
+        .iter()
+        .find_map(|name| property(name))
+    {
+        return Some(event_id);
+    }
+
+    if settings.deterministic_event_id {
+        return property(ORDER_ID_PROPERTY)
+            .map(|order_id| hash_value(&format!("{}:{}", event_name, order_id)));
+    }
+
+    None
+}
+
 /// Parse value
 ///
 /// This function is used to parse the value of a property.
 /// It converts the value to a JSON value.
-/// TODO: add object and array support
+/// JSON-encoded arrays and objects (e.g. `["sku1","sku2"]`) are parsed, as long as they are
+/// shorter than `MAX_JSON_VALUE_LENGTH` bytes and `MAX_JSON_VALUE_DEPTH` levels deep;
+/// otherwise, or when they are invalid, they are kept as strings.
 pub(crate) fn parse_value(value: &str) -> serde_json::Value {
-    if value == "true" {
+    if let Some(json) = parse_json_container(value) {
+        json
+    } else if value == "true" {
         serde_json::Value::from(true)
     } else if value == "false" {
         serde_json::Value::from(false)
-    } else if value.parse::<f64>().is_ok() {
-        serde_json::Value::Number(value.parse().unwrap())
+    } else if let Some(number) = parse_number(value) {
+        serde_json::Value::Number(number)
     } else {
         serde_json::Value::String(value.to_string())
     }
 }
 
+/// Parse a JSON number. Numbers with leading zeros (e.g. `000123`) and integers
+/// that can't be represented without losing precision are not parsed.
+fn parse_number(value: &str) -> Option<serde_json::Number> {
+    let number: serde_json::Number = value.parse().ok()?;
+    if number.is_f64() && !value.contains(['.', 'e', 'E']) {
+        return None;
+    }
+    Some(number)
+}
+
+/// Parse a JSON-encoded array or object, within the size and depth limits.
+fn parse_json_container(value: &str) -> Option<serde_json::Value> {
+    let trimmed = value.trim();
+    let is_container = (trimmed.starts_with('[') && trimmed.ends_with(']'))
+        || (trimmed.starts_with('{') && trimmed.ends_with('}'));
+    if !is_container || trimmed.len() > MAX_JSON_VALUE_LENGTH {
+        return None;
+    }
+
+    let json: serde_json::Value = serde_json::from_str(trimmed).ok()?;
+    if json_depth(&json) > MAX_JSON_VALUE_DEPTH {
+        return None;
+    }
+    Some(json)
+}
+
+/// Nesting depth of a JSON value, scalars having a depth of 0.
+fn json_depth(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Array(items) => 1 + items.iter().map(json_depth).max().unwrap_or(0),
+        serde_json::Value::Object(map) => 1 + map.values().map(json_depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Insert products
+///
+/// This function is used to convert the products of a track event into Snapchat custom data.
+/// It fills `contents`, `item_ids` and `num_items`, and computes `value` and `currency`
+/// from the product prices when they are not explicitly set in the event properties.
+///
+/// Quantities must be non-negative integers (`1` by default): `num_items` is not computed
+/// when a quantity is invalid. Prices must be finite non-negative numbers. `value` is only
+/// computed when every product has a valid price and quantity, and when the products don't
+/// mix currencies.
+pub(crate) fn insert_products(
+    custom_data: &mut HashMap<String, serde_json::Value>,
+    products: &[Dict],
+) {
+    if products.is_empty() {
+        return;
+    }
+
+    let mut contents = vec![];
+    let mut item_ids = vec![];
+    let mut num_items: Option<u64> = Some(0);
+    let mut value: Option<f64> = Some(0.0);
+    let mut currencies: Vec<String> = vec![];
+
+    for product in products.iter() {
+        let product: HashMap<&str, &str> = product
+            .iter()
+            .filter(|(_, v)| !v.trim().is_empty())
+            .map(|(k, v)| (k.as_str(), v.trim()))
+            .collect();
+
+        let id = ["product_id", "sku", "id"]
+            .iter()
+            .find_map(|key| product.get(key));
+        let quantity = match product.get("quantity") {
+            Some(quantity) => parse_quantity(quantity),
+            None => Some(1),
+        };
+        let price = product.get("price").and_then(|p| parse_price(p));
+
+        num_items = num_items.zip(quantity).map(|(n, q)| n.saturating_add(q));
+        value = match (value, price, quantity) {
+            (Some(value), Some(price), Some(quantity)) => Some(value + price * quantity as f64),
+            _ => None,
+        };
+        if let Some(currency) = product.get("currency").map(|c| c.to_uppercase()) {
+            if !currencies.contains(&currency) {
+                currencies.push(currency);
+            }
+        }
+
+        let Some(id) = id else {
+            continue;
+        };
+
+        let mut content = serde_json::Map::new();
+        content.insert("id".to_string(), serde_json::Value::from(*id));
+        if let Some(quantity) = quantity {
+            content.insert("quantity".to_string(), serde_json::Value::from(quantity));
+        }
+        if let Some(price) = price {
+            content.insert("item_price".to_string(), serde_json::Value::from(price));
+        }
+        for key in ["brand", "category"] {
+            if let Some(v) = product.get(key) {
+                content.insert(key.to_string(), serde_json::Value::from(*v));
+            }
+        }
+        contents.push(serde_json::Value::Object(content));
+        item_ids.push(serde_json::Value::from(*id));
+    }
+
+    let currency = match currencies.as_slice() {
+        [] => None,
+        [currency] => Some(currency.clone()),
+        // the prices can't be summed up
+        _ => {
+            value = None;
+            None
+        }
+    };
+
+    if !contents.is_empty() {
+        custom_data
+            .entry("contents".to_string())
+            .or_insert(serde_json::Value::Array(contents));
+        custom_data
+            .entry("item_ids".to_string())
+            .or_insert(serde_json::Value::Array(item_ids));
+    }
+    if let Some(num_items) = num_items {
+        custom_data
+            .entry("num_items".to_string())
+            .or_insert(serde_json::Value::from(num_items));
+    }
+    if let Some(value) = value.filter(|value| value.is_finite()) {
+        // round to cents to avoid floating point noise (e.g. 59.970000000000006)
+        custom_data
+            .entry("value".to_string())
+            .or_insert(serde_json::Value::from((value * 100.0).round() / 100.0));
+    }
+    if let Some(currency) = currency {
+        custom_data
+            .entry("currency".to_string())
+            .or_insert(serde_json::Value::from(currency));
+    }
+}
+
+/// Parse a product price, which must be a finite non-negative number.
+fn parse_price(value: &str) -> Option<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|price| price.is_finite() && *price >= 0.0)
+}
+
+/// Parse a product quantity, which must be a non-negative integer (e.g. `2` or `2.0`).
+fn parse_quantity(value: &str) -> Option<u64> {
+    value.parse::<u64>().ok().or_else(|| {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|q| q.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(q))
+            .map(|q| q as u64)
+    })
+}
+
+/// Hash user property
+///
+/// This function is used to hash a user property according to its hash policy.
+/// Values are normalized with the given function before being hashed.
+pub(crate) fn hash_user_property(
+    value: &str,
+    policy: HashPolicy,
+    normalize: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    match policy {
+        HashPolicy::Auto => {
+            prehashed_value(value).or_else(|| normalize(value).map(|v| hash_value(&v)))
+        }
+        HashPolicy::Force => normalize(value)
+            .or_else(|| Some(value.trim().to_string()).filter(|v| !v.is_empty()))
+            .map(|v| hash_value(&v)),
+        HashPolicy::Passthrough => {
+            let value = value.trim();
+            let value = strip_prefix_ignore_case(value, "sha256:").unwrap_or(value);
+            Some(value.to_string()).filter(|v| !v.is_empty())
+        }
+    }
+}
+
+/// Prehashed value
+///
+/// Returns the SHA256 digest if the value is already hashed: a 64-character lowercase hex digest,
+/// or a hex digest explicitly marked with a `sha256:` prefix.
+pub(crate) fn prehashed_value(value: &str) -> Option<String> {
+    let value = value.trim();
+    let is_digest = |v: &str| v.len() == 64 && v.chars().all(|c| c.is_ascii_hexdigit());
+
+    if let Some(digest) = strip_prefix_ignore_case(value, "sha256:") {
+        return Some(digest.to_lowercase()).filter(|d| is_digest(d));
+    }
+
+    if is_digest(value) && !value.chars().any(|c| c.is_ascii_uppercase()) {
+        return Some(value.to_string());
+    }
+
+    None
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    if value.len() >= prefix.len()
+        && value.is_char_boundary(prefix.len())
+        && value[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&value[prefix.len()..])
+    } else {
+        None
+    }
+}
+
 /// SHA256 hash value
 ///
 /// This function is used to hash the value.
@@ -217,3 +752,413 @@ pub(crate) fn hash_value(input: &str) -> String {
     hasher.update(input.as_bytes());
     format!("{:x}", hasher.finalize())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn product(fields: &[(&str, &str)]) -> Dict {
+        fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn insert_products_with_multi_product_cart() {
+        let products = vec![
+            product(&[
+                ("product_id", "sku-1"),
+                ("price", "19.99"),
+                ("quantity", "3"),
+                ("brand", "Acme"),
+                ("category", "shoes"),
+                ("currency", "eur"),
+            ]),
+            product(&[("sku", "sku-2"), ("price", "5"), ("quantity", "2")]),
+            product(&[("id", "sku-3"), ("price", "0.1")]),
+        ];
+        let mut custom_data = HashMap::new();
+        insert_products(&mut custom_data, &products);
+
+        assert_eq!(
+            custom_data.get("contents").unwrap(),
+            &json!([
+                {"id": "sku-1", "quantity": 3, "item_price": 19.99, "brand": "Acme", "category": "shoes"},
+                {"id": "sku-2", "quantity": 2, "item_price": 5.0},
+                {"id": "sku-3", "quantity": 1, "item_price": 0.1},
+            ])
+        );
+        assert_eq!(
+            custom_data.get("item_ids").unwrap(),
+            &json!(["sku-1", "sku-2", "sku-3"])
+        );
+        assert_eq!(custom_data.get("num_items").unwrap(), &json!(6));
+        assert_eq!(custom_data.get("value").unwrap(), &json!(70.07));
+        assert_eq!(custom_data.get("currency").unwrap(), &json!("EUR"));
+    }
+
+    #[test]
+    fn insert_products_keeps_explicit_properties() {
+        let products = vec![
+            product(&[
+                ("product_id", "sku-1"),
+                ("price", "10"),
+                ("currency", "EUR"),
+            ]),
+            product(&[("product_id", "sku-2"), ("price", "20")]),
+        ];
+        let mut custom_data = HashMap::new();
+        custom_data.insert("value".to_string(), json!(25));
+        custom_data.insert("currency".to_string(), json!("USD"));
+        insert_products(&mut custom_data, &products);
+
+        assert_eq!(custom_data.get("value").unwrap(), &json!(25));
+        assert_eq!(custom_data.get("currency").unwrap(), &json!("USD"));
+        assert_eq!(custom_data.get("num_items").unwrap(), &json!(2));
+    }
+
+    #[test]
+    fn insert_products_without_ids_or_prices() {
+        let products = vec![
+            product(&[("name", "gift card"), ("quantity", "2")]),
+            product(&[("product_id", "sku-2"), ("price", "not a price")]),
+        ];
+        let mut custom_data = HashMap::new();
+        insert_products(&mut custom_data, &products);
+
+        assert_eq!(
+            custom_data.get("contents").unwrap(),
+            &json!([{"id": "sku-2", "quantity": 1}])
+        );
+        assert_eq!(custom_data.get("item_ids").unwrap(), &json!(["sku-2"]));
+        assert_eq!(custom_data.get("num_items").unwrap(), &json!(3));
+        assert_eq!(custom_data.get("value"), None);
+        assert_eq!(custom_data.get("currency"), None);
+    }
+
+    #[test]
+    fn insert_products_with_empty_cart() {
+        let mut custom_data = HashMap::new();
+        insert_products(&mut custom_data, &[]);
+        assert!(custom_data.is_empty());
+    }
+
+    #[test]
+    fn insert_products_with_invalid_quantities() {
+        let products = vec![
+            product(&[
+                ("product_id", "sku-1"),
+                ("price", "10"),
+                ("quantity", "1.5"),
+            ]),
+            product(&[("product_id", "sku-2"), ("price", "5"), ("quantity", "2.0")]),
+            product(&[("product_id", "sku-3"), ("price", "5"), ("quantity", "-1")]),
+        ];
+        let mut custom_data = HashMap::new();
+        insert_products(&mut custom_data, &products);
+
+        assert_eq!(
+            custom_data.get("contents").unwrap(),
+            &json!([
+                {"id": "sku-1", "item_price": 10.0},
+                {"id": "sku-2", "quantity": 2, "item_price": 5.0},
+                {"id": "sku-3", "item_price": 5.0},
+            ])
+        );
+        assert_eq!(custom_data.get("num_items"), None);
+        assert_eq!(custom_data.get("value"), None);
+    }
+
+    #[test]
+    fn insert_products_with_missing_prices() {
+        let products = vec![
+            product(&[
+                ("product_id", "sku-1"),
+                ("price", "10"),
+                ("currency", "EUR"),
+            ]),
+            product(&[("product_id", "sku-2"), ("quantity", "2")]),
+        ];
+        let mut custom_data = HashMap::new();
+        insert_products(&mut custom_data, &products);
+
+        assert_eq!(custom_data.get("num_items").unwrap(), &json!(3));
+        assert_eq!(custom_data.get("value"), None);
+        assert_eq!(custom_data.get("currency").unwrap(), &json!("EUR"));
+    }
+
+    #[test]
+    fn insert_products_with_invalid_prices() {
+        for price in ["NaN", "inf", "-inf", "-5", "1e400"] {
+            let products = vec![
+                product(&[("product_id", "sku-1"), ("price", price)]),
+                product(&[("product_id", "sku-2"), ("price", "10")]),
+            ];
+            let mut custom_data = HashMap::new();
+            insert_products(&mut custom_data, &products);
+
+            assert_eq!(
+                custom_data.get("contents").unwrap(),
+                &json!([
+                    {"id": "sku-1", "quantity": 1},
+                    {"id": "sku-2", "quantity": 1, "item_price": 10.0},
+                ]),
+                "price: {}",
+                price
+            );
+            assert_eq!(custom_data.get("value"), None, "price: {}", price);
+        }
+    }
+
+    #[test]
+    fn insert_products_with_mixed_currencies() {
+        let products = vec![
+            product(&[
+                ("product_id", "sku-1"),
+                ("price", "10"),
+                ("currency", "EUR"),
+            ]),
+            product(&[
+                ("product_id", "sku-2"),
+                ("price", "20"),
+                ("currency", "usd"),
+            ]),
+            product(&[
+                ("product_id", "sku-3"),
+                ("price", "30"),
+                ("currency", "eur"),
+            ]),
+        ];
+        let mut custom_data = HashMap::new();
+        insert_products(&mut custom_data, &products);
+
+        assert_eq!(custom_data.get("num_items").unwrap(), &json!(3));
+        assert_eq!(custom_data.get("value"), None);
+        assert_eq!(custom_data.get("currency"), None);
+    }
+
+    #[test]
+    fn parse_quantity_table() {
+        let cases = [
+            ("3", Some(3)),
+            ("0", Some(0)),
+            ("3.0", Some(3)),
+            ("3.5", None),
+            ("-1", None),
+            ("1e2", Some(100)),
+            ("inf", None),
+            ("NaN", None),
+            ("three", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_quantity(value), expected, "value: {}", value);
+        }
+    }
+
+    #[test]
+    fn action_source_table() {
+        let cases = [
+            ("WEB", Some(ActionSource::Web)),
+            ("web", Some(ActionSource::Web)),
+            ("MOBILE_APP", Some(ActionSource::MobileApp)),
+            (" mobile-app ", Some(ActionSource::MobileApp)),
+            ("app", Some(ActionSource::MobileApp)),
+            ("OFFLINE", Some(ActionSource::Offline)),
+            ("STORE", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(ActionSource::parse(input), expected, "input: {:?}", input);
+        }
+        assert_eq!(
+            serde_json::to_string(&ActionSource::MobileApp).unwrap(),
+            "\"MOBILE_APP\""
+        );
+    }
+
+    #[test]
+    fn prehashed_value_table() {
+        let digest = hash_value("john.doe@example.com");
+        let cases = [
+            (digest.clone(), Some(digest.clone())),
+            (format!(" {} ", digest), Some(digest.clone())),
+            (format!("sha256:{}", digest), Some(digest.clone())),
+            (
+                format!("SHA256:{}", digest.to_uppercase()),
+                Some(digest.clone()),
+            ),
+            (digest.to_uppercase(), None),
+            (digest[..63].to_string(), None),
+            ("sha256:not-a-digest".to_string(), None),
+            ("john.doe@example.com".to_string(), None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(prehashed_value(&input), expected, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn hash_user_property_with_policies() {
+        let digest = hash_value("john.doe@example.com");
+        let email = " John.Doe@Example.com ";
+        let normalize = crate::normalize::normalize_email;
+
+        // auto: raw values are normalized and hashed, hashed values are forwarded
+        assert_eq!(
+            hash_user_property(email, HashPolicy::Auto, normalize),
+            Some(digest.clone())
+        );
+        assert_eq!(
+            hash_user_property(&digest, HashPolicy::Auto, normalize),
+            Some(digest.clone())
+        );
+        assert_eq!(
+            hash_user_property(&format!("sha256:{}", digest), HashPolicy::Auto, normalize),
+            Some(digest.clone())
+        );
+
+        // force: values are always hashed
+        assert_eq!(
+            hash_user_property(email, HashPolicy::Force, normalize),
+            Some(digest.clone())
+        );
+        assert_eq!(
+            hash_user_property(&digest, HashPolicy::Force, |v| Some(v.to_string())),
+            Some(hash_value(&digest))
+        );
+        // force: values that can't be normalized are hashed trimmed
+        assert_eq!(
+            hash_user_property(&format!(" {} ", digest), HashPolicy::Force, normalize),
+            Some(hash_value(&digest))
+        );
+        assert_eq!(hash_user_property("  ", HashPolicy::Force, normalize), None);
+
+        // passthrough: values are forwarded unchanged
+        assert_eq!(
+            hash_user_property(
+                &format!("sha256:{}", digest),
+                HashPolicy::Passthrough,
+                normalize
+            ),
+            Some(digest.clone())
+        );
+        assert_eq!(
+            hash_user_property("custom-hash", HashPolicy::Passthrough, normalize),
+            Some("custom-hash".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_value_table() {
+        let cases = [
+            ("true", json!(true)),
+            ("false", json!(false)),
+            ("12.5", json!(12.5)),
+            ("-3", json!(-3)),
+            ("000123", json!("000123")),
+            ("12345678901234567890123", json!("12345678901234567890123")),
+            ("1e3", json!(1000.0)),
+            ("NaN", json!("NaN")),
+            ("abc", json!("abc")),
+            (r#"["sku1","sku2"]"#, json!(["sku1", "sku2"])),
+            (r#" [1, 2.5, true] "#, json!([1, 2.5, true])),
+            ("[]", json!([])),
+            (
+                r#"{"id":"sku1","quantity":2}"#,
+                json!({"id": "sku1", "quantity": 2}),
+            ),
+            (
+                r#"[{"id":"sku1","tags":["a","b"]},{"id":"sku2","meta":{"size":"M"}}]"#,
+                json!([{"id": "sku1", "tags": ["a", "b"]}, {"id": "sku2", "meta": {"size": "M"}}]),
+            ),
+            // invalid JSON is kept as a string
+            ("[sku1, sku2]", json!("[sku1, sku2]")),
+            (r#"{"id":"sku1""#, json!(r#"{"id":"sku1""#)),
+            ("[abc}", json!("[abc}")),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_value(value), expected, "value: {}", value);
+        }
+    }
+
+    #[test]
+    fn parse_value_with_limits() {
+        let deep = "[[[[[1]]]]]";
+        assert_eq!(parse_value(deep), json!([[[[[1]]]]]));
+        let too_deep = "[[[[[[1]]]]]]";
+        assert_eq!(parse_value(too_deep), json!(too_deep));
+
+        let too_long = format!("[{}]", vec!["1"; MAX_JSON_VALUE_LENGTH].join(","));
+        assert_eq!(parse_value(&too_long), json!(too_long));
+    }
+
+    #[test]
+    fn event_id_table() {
+        let properties = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let default = Settings {
+            event_id_properties: vec!["event_id".to_string()],
+            ..Settings::default()
+        };
+        let configured = Settings {
+            event_id_properties: vec!["transaction_id".to_string(), "order_id".to_string()],
+            deterministic_event_id: true,
+            ..Settings::default()
+        };
+        let deterministic = Settings {
+            event_id_properties: vec!["event_id".to_string()],
+            deterministic_event_id: true,
+            ..Settings::default()
+        };
+
+        let cases = [
+            (
+                &default,
+                properties(&[("event_id", " evt-1 ")]),
+                Some("evt-1".to_string()),
+            ),
+            (
+                &default,
+                properties(&[("event_id", " "), ("order_id", "1234")]),
+                None,
+            ),
+            (&default, properties(&[]), None),
+            (
+                &configured,
+                properties(&[("order_id", "1234"), ("transaction_id", "tx-1")]),
+                Some("tx-1".to_string()),
+            ),
+            (
+                &configured,
+                properties(&[("event_id", "evt-1"), ("order_id", "1234")]),
+                Some("1234".to_string()),
+            ),
+            (
+                &deterministic,
+                properties(&[("order_id", "1234")]),
+                Some(hash_value("PURCHASE:1234")),
+            ),
+            (
+                &deterministic,
+                properties(&[("event_id", "evt-1"), ("order_id", "1234")]),
+                Some("evt-1".to_string()),
+            ),
+            (&deterministic, properties(&[("value", "10")]), None),
+        ];
+        for (settings, properties, expected) in cases {
+            assert_eq!(
+                event_id(&properties, "PURCHASE", settings),
+                expected,
+                "properties: {:?}",
+                properties
+            );
+        }
+    }
+}
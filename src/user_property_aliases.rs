@@ -0,0 +1,190 @@
+use crate::error::SnapchatError;
+use std::collections::HashMap;
+
+/// User data fields
+///
+/// The user property names recognised by the component, each filling a Snapchat `user_data` field.
+pub(crate) const USER_DATA_FIELDS: &[&str] = &[
+    "email",
+    "phone_number",
+    "first_name",
+    "last_name",
+    "gender",
+    "date_of_birth",
+    "city",
+    "state",
+    "zip_code",
+    "country",
+    "sc_click_id",
+    "sc_cookie1",
+    "madid",
+    "advertiser_tracking_enabled",
+];
+
+/// Built-in aliases
+///
+/// Common user property names (camelCase forms and synonyms) translated to a user data field
+/// without any configuration. Aliases are stored normalized, see `normalize_key`.
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("aaid", "madid"),
+    ("advertisingid", "madid"),
+    ("birthdate", "date_of_birth"),
+    ("birthday", "date_of_birth"),
+    ("dob", "date_of_birth"),
+    ("emailaddress", "email"),
+    ("familyname", "last_name"),
+    ("gaid", "madid"),
+    ("givenname", "first_name"),
+    ("idfa", "madid"),
+    ("mail", "email"),
+    ("mobile", "phone_number"),
+    ("mobilenumber", "phone_number"),
+    ("mobilephone", "phone_number"),
+    ("phone", "phone_number"),
+    ("postalcode", "zip_code"),
+    ("postcode", "zip_code"),
+    ("sex", "gender"),
+    ("surname", "last_name"),
+    ("telephone", "phone_number"),
+    ("zip", "zip_code"),
+];
+
+/// User property aliases
+///
+/// Translates user property names to user data fields, before normalization and hashing.
+/// Matching ignores case, underscores, dashes and spaces (`emailAddress`, `email_address`
+/// and `Email Address` are equivalent), and the lookup order is:
+/// - the `snapchat_user_property_aliases` setting (e.g. `contact_mail:email,cell:phone_number`)
+/// - the user data field names (e.g. `zipCode` -> `zip_code`)
+/// - the built-in aliases (e.g. `postal_code` -> `zip_code`)
+///
+/// Other user properties are ignored.
+#[derive(Debug, Default)]
+pub(crate) struct UserPropertyAliases {
+    aliases: HashMap<String, &'static str>,
+}
+
+impl UserPropertyAliases {
+    pub fn new(aliases: Option<&String>) -> Result<Self, SnapchatError> {
+        let mut user_property_aliases = UserPropertyAliases::default();
+
+        if let Some(aliases) = aliases {
+            for (from, to) in
+                crate::settings::parse_setting_pairs("snapchat_user_property_aliases", aliases)?
+            {
+                let field = user_data_field(&to).ok_or_else(|| {
+                    SnapchatError::invalid_setting(
+                        "snapchat_user_property_aliases",
+                        format!(
+                            "unknown field {} (expected one of {})",
+                            to,
+                            USER_DATA_FIELDS.join(", ")
+                        ),
+                    )
+                })?;
+                user_property_aliases
+                    .aliases
+                    .insert(normalize_key(&from), field);
+            }
+        }
+
+        Ok(user_property_aliases)
+    }
+
+    /// Resolve the user data field of a user property name, from the configured aliases only.
+    pub fn resolve_configured(&self, key: &str) -> Option<&'static str> {
+        self.aliases.get(&normalize_key(key)).copied()
+    }
+
+    /// Resolve the user data field of a user property name.
+    /// Returns `None` when the property doesn't match any field.
+    pub fn resolve(&self, key: &str) -> Option<&'static str> {
+        let normalized = normalize_key(key);
+        if let Some(field) = self.aliases.get(&normalized) {
+            return Some(field);
+        }
+
+        user_data_field(key).or_else(|| {
+            BUILTIN_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == normalized)
+                .map(|(_, field)| *field)
+        })
+    }
+}
+
+/// Normalize a property name for matching: lowercase, without underscores, dashes and spaces.
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| !matches!(c, '_' | '-' | ' '))
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Returns the user data field matching the given name, if any.
+fn user_data_field(name: &str) -> Option<&'static str> {
+    let normalized = normalize_key(name);
+    USER_DATA_FIELDS
+        .iter()
+        .find(|field| normalize_key(field) == normalized)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn resolves_every_field_in_any_case() {
+        let aliases = UserPropertyAliases::default();
+        for field in USER_DATA_FIELDS {
+            assert_eq!(aliases.resolve(field), Some(*field));
+            assert_eq!(aliases.resolve(&field.to_uppercase()), Some(*field));
+            assert_eq!(aliases.resolve(&field.replace('_', "-")), Some(*field));
+        }
+        assert_eq!(aliases.resolve("phoneNumber"), Some("phone_number"));
+        assert_eq!(aliases.resolve("dateOfBirth"), Some("date_of_birth"));
+    }
+
+    #[test]
+    fn resolves_builtin_aliases() {
+        let aliases = UserPropertyAliases::default();
+        let cases = [
+            ("emailAddress", Some("email")),
+            ("email_address", Some("email")),
+            ("phone", Some("phone_number")),
+            ("mobilePhone", Some("phone_number")),
+            ("postal_code", Some("zip_code")),
+            ("postalCode", Some("zip_code")),
+            ("birthday", Some("date_of_birth")),
+            ("givenName", Some("first_name")),
+            ("IDFA", Some("madid")),
+            ("random_property", None),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(aliases.resolve(key), expected, "key: {}", key);
+        }
+    }
+
+    #[test]
+    fn settings_aliases_take_precedence() {
+        let aliases = UserPropertyAliases::new(Some(
+            &"contact_mail:email, cell:Phone Number, phone:madid".to_string(),
+        ))
+        .unwrap();
+        assert_eq!(aliases.resolve("contactMail"), Some("email"));
+        assert_eq!(aliases.resolve("cell"), Some("phone_number"));
+        assert_eq!(aliases.resolve("phone"), Some("madid"));
+        assert_eq!(aliases.resolve("mobile"), Some("phone_number"));
+        assert_eq!(aliases.resolve_configured("contactMail"), Some("email"));
+        assert_eq!(aliases.resolve_configured("mobile"), None);
+        assert_eq!(aliases.resolve_configured("email"), None);
+    }
+
+    #[test]
+    fn invalid_settings_fail() {
+        assert!(UserPropertyAliases::new(Some(&"contact_mail".to_string())).is_err());
+        assert!(UserPropertyAliases::new(Some(&"contact_mail:mail".to_string())).is_err());
+    }
+}